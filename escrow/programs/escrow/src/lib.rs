@@ -1,51 +1,700 @@
+// Anchor 0.31 `#[program]` emits its IDL handlers at the crate root, next to the program module, and they
+// still call the deprecated `AccountInfo::realloc`. An item-level allow cannot reach generated siblings,
+// so this stays crate-wide; our own code uses `AccountInfo::resize`
+#![allow(deprecated)]
+
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::ed25519_program;
 use anchor_lang::system_program;
+use anchor_spl::associated_token::{get_associated_token_address_with_program_id, AssociatedToken};
+use anchor_spl::token_interface::{self, CloseAccount, Mint, TokenAccount, TokenInterface, TransferChecked};
+use solana_instructions_sysvar::{load_current_index_checked, load_instruction_at_checked};
 
 declare_id!("Edmq5WTFJL5gtwMmD9HdtJ5N14ivXMP4vprvPxRkFZRJ");
 
-// Fixed penalty wallet - hardcoded in contract
-const PENALTY_WALLET: &str = "2c8QGXM2tRMh7yb1Zva48ZmQTPMmLZCu159x2hscxxwv";
+// Current account layout versions, bump when appending fields or changing what stored values mean
+// Accounts created before versioning read as version 0
+const ESCROW_LAYOUT_VERSION: u8 = 1;
+const STAKE_RECORD_LAYOUT_VERSION: u8 = 1;
 
-fn get_penalty_wallet() -> Pubkey {
-    Pubkey::try_from(PENALTY_WALLET).unwrap()
-}
+// Maximum number of arbiters on a dispute panel
+const MAX_ARBITERS: usize = 10;
+
+// Maximum number of itemized deductions on a slash, resolution or move-out settlement
+const MAX_DEDUCTIONS: usize = 10;
+
+// Maximum length of the evidence URI on a deduction
+const MAX_EVIDENCE_URI_LEN: usize = 200;
+
+// Hard cap on the protocol fee taken from resolved stakes (5%)
+const MAX_RESOLUTION_FEE_BPS: u16 = 500;
+
+// Basis points denominator (10_000 bps = 100%)
+const BPS_DENOMINATOR: u64 = 10_000;
+
+// Minimum time between start_lease and a resolution for the lease to count towards reputation (30 days)
+const MIN_COMPLETED_LEASE_DURATION: i64 = 30 * 24 * 60 * 60;
+
+// Dispute window of legacy escrows migrated without their lessor (3 days)
+const LEGACY_DISPUTE_WINDOW: i64 = 3 * 24 * 60 * 60;
 
-// Helper function to hash strings for PDA generation
-fn hashString(input: &str) -> [u8; 32] {
+// Hash a string the same way clients derive PDA seeds from it (sha256)
+fn hash_string(input: &str) -> [u8; 32] {
     use anchor_lang::solana_program::hash::{hash, Hash};
     let hash_result: Hash = hash(input.as_bytes());
     hash_result.to_bytes()
 }
 
+fn apply_bps(amount: u64, bps: u16) -> Result<u64> {
+    require!(bps as u64 <= BPS_DENOMINATOR, EscrowError::InvalidBasisPoints);
+    let result = (amount as u128)
+        .checked_mul(bps as u128)
+        .ok_or(EscrowError::ArithmeticOverflow)?
+        / BPS_DENOMINATOR as u128;
+    Ok(result as u64)
+}
+
+// Move lamports out of a program-owned PDA (escrow or treasury), no CPI needed
+fn transfer_from_escrow(escrow_info: &AccountInfo, recipient: &AccountInfo, amount: u64) -> Result<()> {
+    if amount == 0 {
+        return Ok(());
+    }
+    **escrow_info.try_borrow_mut_lamports()? -= amount;
+    **recipient.try_borrow_mut_lamports()? += amount;
+    Ok(())
+}
+
+// Token side of an escrow whose stakes are held in an SPL / Token-2022 mint
+struct StakeVault<'a, 'info> {
+    mint: &'a InterfaceAccount<'info, Mint>,
+    vault: &'a InterfaceAccount<'info, TokenAccount>,
+    token_program: &'a Interface<'info, TokenInterface>,
+}
+
+// Check the optional token accounts against the escrow's stake mint
+// Returns None for native SOL escrows
+fn load_stake_vault<'a, 'info>(
+    escrow_account: &Account<'info, ApartmentEscrow>,
+    stake_mint: Option<&'a InterfaceAccount<'info, Mint>>,
+    stake_vault: Option<&'a InterfaceAccount<'info, TokenAccount>>,
+    token_program: Option<&'a Interface<'info, TokenInterface>>,
+) -> Result<Option<StakeVault<'a, 'info>>> {
+    let Some(stake_mint_key) = escrow_account.stake_mint else {
+        return Ok(None);
+    };
+
+    let mint = stake_mint.ok_or(EscrowError::MissingTokenAccount)?;
+    let vault = stake_vault.ok_or(EscrowError::MissingTokenAccount)?;
+    let token_program = token_program.ok_or(EscrowError::MissingTokenAccount)?;
+
+    require!(mint.key() == stake_mint_key, EscrowError::InvalidStakeMint);
+    require!(
+        vault.key() == get_associated_token_address_with_program_id(
+            &escrow_account.key(),
+            &stake_mint_key,
+            &token_program.key(),
+        ),
+        EscrowError::InvalidStakeVault
+    );
+
+    Ok(Some(StakeVault { mint, vault, token_program }))
+}
+
+// Wallet owed a payout from the escrow
+// SOL escrows pay the wallet itself, token escrows pay its token account for the stake mint
+struct PayoutRecipient<'a, 'info> {
+    wallet: Pubkey,
+    wallet_info: Option<&'a AccountInfo<'info>>,
+    token_account: Option<&'a InterfaceAccount<'info, TokenAccount>>,
+}
+
+// Pay out of the escrow in its stake asset
+// Lamports move straight off the PDA, tokens leave the vault with the escrow PDA signing
+fn pay_from_escrow<'info>(
+    escrow_account: &Account<'info, ApartmentEscrow>,
+    apartment_hash: &[u8; 32],
+    stake_vault: Option<&StakeVault<'_, 'info>>,
+    recipient: &PayoutRecipient<'_, 'info>,
+    amount: u64,
+) -> Result<()> {
+    if amount == 0 {
+        return Ok(());
+    }
+
+    let Some(stake_vault) = stake_vault else {
+        let wallet_info = recipient.wallet_info.ok_or(EscrowError::MissingPayoutAccount)?;
+        require!(wallet_info.key() == recipient.wallet, EscrowError::InvalidPayoutAccount);
+        return transfer_from_escrow(&escrow_account.to_account_info(), wallet_info, amount);
+    };
+
+    let token_account = recipient.token_account.ok_or(EscrowError::MissingTokenAccount)?;
+    require!(token_account.owner == recipient.wallet, EscrowError::InvalidPayoutAccount);
+    require!(token_account.mint == stake_vault.mint.key(), EscrowError::InvalidStakeMint);
+
+    let bump = [escrow_account.bump];
+    let escrow_seeds: [&[u8]; 3] = [b"escrow", apartment_hash.as_ref(), &bump];
+    let signer_seeds = [&escrow_seeds[..]];
+
+    let cpi_context = CpiContext::new_with_signer(
+        stake_vault.token_program.to_account_info(),
+        TransferChecked {
+            from: stake_vault.vault.to_account_info(),
+            mint: stake_vault.mint.to_account_info(),
+            to: token_account.to_account_info(),
+            authority: escrow_account.to_account_info(),
+        },
+        &signer_seeds,
+    );
+    token_interface::transfer_checked(cpi_context, amount, stake_vault.mint.decimals)
+}
+
+// Check a list of itemized deductions and return their total
+fn total_deductions(deductions: &[DeductionItem]) -> Result<u64> {
+    require!(deductions.len() <= MAX_DEDUCTIONS, EscrowError::TooManyDeductions);
+
+    let mut total: u64 = 0;
+    for deduction in deductions {
+        require!(deduction.amount > 0, EscrowError::InvalidAmount);
+        require!(deduction.uri.len() <= MAX_EVIDENCE_URI_LEN, EscrowError::EvidenceUriTooLong);
+        total = total
+            .checked_add(deduction.amount)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+    }
+
+    Ok(total)
+}
+
+// Last moment the lessor can propose move-out deductions from a deposit
+fn move_out_deadline(escrow_account: &ApartmentEscrow, stake_record: &StakeRecord) -> Result<i64> {
+    stake_record.lease_end
+        .checked_add(escrow_account.dispute_window)
+        .ok_or(EscrowError::ArithmeticOverflow.into())
+}
+
+// Lessor, penalty wallet and referrer parts of a slashed amount
+struct PenaltyShares {
+    lessor: u64,
+    penalty_wallet: u64,
+    referrer: u64,
+}
+
+// Split a slashed amount using the escrow's slash split
+// Rounding dust and the referrer part (when nobody referred the tenant) go to the penalty wallet
+fn split_penalty(amount: u64, slash_split: &SlashSplit, has_referrer: bool) -> Result<PenaltyShares> {
+    let lessor = apply_bps(amount, slash_split.lessor_bps)?;
+    let referrer = if has_referrer {
+        apply_bps(amount, slash_split.referrer_bps)?
+    } else {
+        0
+    };
+    let penalty_wallet = amount
+        .checked_sub(lessor)
+        .and_then(|rest| rest.checked_sub(referrer))
+        .ok_or(EscrowError::ArithmeticOverflow)?;
+
+    Ok(PenaltyShares { lessor, penalty_wallet, referrer })
+}
+
+// Slash split shares must add up to the whole slashed amount
+fn require_valid_slash_split(slash_split: &SlashSplit) -> Result<()> {
+    let split_total = slash_split.lessor_bps as u64
+        + slash_split.penalty_wallet_bps as u64
+        + slash_split.referrer_bps as u64;
+    require!(split_total == BPS_DENOMINATOR, EscrowError::InvalidSlashSplit);
+    Ok(())
+}
+
+// Pay out all parts of a slashed amount from the escrow
+fn pay_penalty_shares<'info>(
+    escrow_account: &Account<'info, ApartmentEscrow>,
+    apartment_hash: &[u8; 32],
+    stake_vault: Option<&StakeVault<'_, 'info>>,
+    lessor: &PayoutRecipient<'_, 'info>,
+    penalty_wallet: &PayoutRecipient<'_, 'info>,
+    referrer: Option<&PayoutRecipient<'_, 'info>>,
+    shares: &PenaltyShares,
+) -> Result<()> {
+    pay_from_escrow(escrow_account, apartment_hash, stake_vault, lessor, shares.lessor)?;
+    pay_from_escrow(escrow_account, apartment_hash, stake_vault, penalty_wallet, shares.penalty_wallet)?;
+
+    if shares.referrer > 0 {
+        let referrer = referrer.ok_or(EscrowError::MissingReferrerAccount)?;
+        pay_from_escrow(escrow_account, apartment_hash, stake_vault, referrer, shares.referrer)?;
+    }
+
+    msg!(
+        "Paid slash shares - lessor: {}, penalty wallet: {}, referrer: {}",
+        shares.lessor,
+        shares.penalty_wallet,
+        shares.referrer
+    );
+
+    Ok(())
+}
+
+// New escrows and stakes are blocked while the program is paused
+fn require_not_paused(config: &ProtocolConfig) -> Result<()> {
+    require!(!config.paused, EscrowError::ProgramPaused);
+    Ok(())
+}
+
+// Lessor-driven payouts are blocked only when the pause also covers payouts
+// Staker self-service (withdrawals, expired refunds, disputes) always stays open
+fn require_payouts_allowed(config: &ProtocolConfig) -> Result<()> {
+    require!(!(config.paused && config.payouts_paused), EscrowError::ProgramPaused);
+    Ok(())
+}
+
+// Minimum stake for a profile after its reputation discount
+// Only profiles that were never slashed earn a discount, and a completed stake only counts when the
+// profile has held at least this escrow's required stake for each one
+// Reputation tracks lamports only, so token escrows never discount
+fn discounted_required_stake(escrow_account: &ApartmentEscrow, reputation: Option<&Reputation>) -> Result<u64> {
+    let stake_discount = &escrow_account.stake_discount;
+    let discount_bps = match reputation {
+        Some(reputation) if reputation.slash_count == 0 && escrow_account.stake_mint.is_none() => {
+            let backed_stakes = reputation.total_resolved
+                .checked_div(escrow_account.required_stake)
+                .unwrap_or(0);
+            (reputation.completed_stakes as u64)
+                .min(backed_stakes)
+                .saturating_mul(stake_discount.bps_per_completed_stake as u64)
+                .min(stake_discount.max_discount_bps as u64) as u16
+        }
+        _ => 0,
+    };
+
+    let discount = apply_bps(escrow_account.required_stake, discount_bps)?;
+    let required_stake = escrow_account.required_stake
+        .checked_sub(discount)
+        .ok_or(EscrowError::ArithmeticOverflow)?;
+    Ok(required_stake)
+}
+
+// Record a lease the approved tenant saw through on their reputation
+// A lessor's own stake says nothing about them as a tenant, and neither does a lease ended right after
+// it started, so leases shorter than MIN_COMPLETED_LEASE_DURATION are not counted
+// Lamport totals only track SOL escrows
+fn record_completed_stake(reputation: &mut Reputation, escrow_account: &ApartmentEscrow, amount: u64) -> Result<()> {
+    if reputation.owner == escrow_account.lessor {
+        return Ok(());
+    }
+    let lease_duration = Clock::get()?
+        .unix_timestamp
+        .checked_sub(escrow_account.lease_started_at)
+        .ok_or(EscrowError::ArithmeticOverflow)?;
+    if lease_duration < MIN_COMPLETED_LEASE_DURATION {
+        msg!("Lease ran {}s, too short to count towards reputation", lease_duration);
+        return Ok(());
+    }
+    reputation.completed_stakes = reputation.completed_stakes
+        .checked_add(1)
+        .ok_or(EscrowError::ArithmeticOverflow)?;
+    if escrow_account.stake_mint.is_none() {
+        reputation.total_resolved = reputation.total_resolved
+            .checked_add(amount)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+    }
+    Ok(())
+}
+
+// Record a slash that stood after its dispute window or the panel's ruling
+fn record_slash(reputation: &mut Reputation, escrow_account: &ApartmentEscrow, amount: u64) -> Result<()> {
+    if reputation.owner == escrow_account.lessor {
+        return Ok(());
+    }
+    reputation.slash_count = reputation.slash_count
+        .checked_add(1)
+        .ok_or(EscrowError::ArithmeticOverflow)?;
+    if escrow_account.stake_mint.is_none() {
+        reputation.total_slashed = reputation.total_slashed
+            .checked_add(amount)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+    }
+    Ok(())
+}
+
+// Only allow an instruction in the listed phases of the escrow lifecycle
+fn require_escrow_status(escrow_account: &ApartmentEscrow, allowed: &[EscrowStatus]) -> Result<()> {
+    require!(allowed.contains(&escrow_account.status), EscrowError::InvalidEscrowStatus);
+    Ok(())
+}
+
+// Move an escrow to a new phase and record the transition
+fn transition_escrow(escrow_account: &mut ApartmentEscrow, new_status: EscrowStatus) {
+    let previous_status = escrow_account.status;
+    escrow_account.status = new_status;
+
+    msg!("Escrow {} moved from {:?} to {:?}", escrow_account.apartment_id, previous_status, new_status);
+
+    emit!(EscrowStatusChanged {
+        apartment_id: escrow_account.apartment_id.clone(),
+        previous_status,
+        new_status,
+    });
+}
+
+// Settling the approved tenant's stake or deposit ends the listing, there is nothing left to lease
+fn end_listing(escrow_account: &mut ApartmentEscrow) {
+    if matches!(
+        escrow_account.status,
+        EscrowStatus::TenantApproved | EscrowStatus::LeaseActive | EscrowStatus::MoveOutReview
+    ) {
+        transition_escrow(escrow_account, EscrowStatus::Closed);
+    }
+}
+
+// Message a tenant signs with their wallet to consent to a referral
+// Clients build the same text, so any change here must be mirrored in the dashboard
+fn referral_consent_message(referrer: &Pubkey, tenant_profile_id: &str, apartment_id: &str) -> Vec<u8> {
+    format!("Referral consent: {} referred {} for apartment {}", referrer, tenant_profile_id, apartment_id)
+        .into_bytes()
+}
+
+// Require the instruction right before this one to be an ed25519 check of `message` signed by `signer`
+// Offsets must point into that instruction's own data (index u16::MAX), not into other instructions
+fn require_ed25519_signature(instructions: &AccountInfo, signer: &Pubkey, message: &[u8]) -> Result<()> {
+    let current_index = load_current_index_checked(instructions)? as usize;
+    require!(current_index > 0, EscrowError::MissingReferralConsent);
+    let instruction = load_instruction_at_checked(current_index - 1, instructions)?;
+    require!(instruction.program_id == ed25519_program::ID, EscrowError::MissingReferralConsent);
+
+    // [num_signatures, padding, signature offset, signature ix, pubkey offset, pubkey ix, message offset, message size, message ix]
+    let data = &instruction.data;
+    require!(data.len() >= 16 && data[0] == 1, EscrowError::InvalidReferralConsent);
+    let read_u16 = |offset: usize| u16::from_le_bytes([data[offset], data[offset + 1]]);
+    require!(
+        read_u16(4) == u16::MAX && read_u16(8) == u16::MAX && read_u16(14) == u16::MAX,
+        EscrowError::InvalidReferralConsent
+    );
+
+    let pubkey_offset = read_u16(6) as usize;
+    let message_offset = read_u16(10) as usize;
+    let message_size = read_u16(12) as usize;
+    let signed_pubkey = data.get(pubkey_offset..pubkey_offset + 32)
+        .ok_or(EscrowError::InvalidReferralConsent)?;
+    let signed_message = data.get(message_offset..message_offset + message_size)
+        .ok_or(EscrowError::InvalidReferralConsent)?;
+    require!(
+        signed_pubkey == signer.as_ref() && signed_message == message,
+        EscrowError::InvalidReferralConsent
+    );
+    Ok(())
+}
+
+// Load the referral registered for a stake, if the referral PDA exists
+// Only referrals the staking wallet consented to count, anything else is ignored
+fn load_referral(referral_info: &AccountInfo, staker: &Pubkey) -> Result<Option<Referral>> {
+    if referral_info.data_is_empty() {
+        return Ok(None);
+    }
+    require!(referral_info.owner == &crate::ID, EscrowError::InvalidReferral);
+
+    let data = referral_info.try_borrow_data()?;
+    let referral = Referral::try_deserialize(&mut &data[..])?;
+    if referral.tenant != *staker {
+        msg!("Ignoring referral consented by {}, not the staker", referral.tenant);
+        return Ok(None);
+    }
+    Ok(Some(referral))
+}
+
 #[program]
 pub mod escrow {
     use super::*;
 
+    /// Initialize protocol config and treasury (program upgrade authority only, once)
+    pub fn initialize_config(
+        ctx: Context<InitializeConfig>,
+        penalty_wallet: Pubkey,
+        resolution_fee_bps: u16,
+    ) -> Result<()> {
+        require!(penalty_wallet != Pubkey::default(), EscrowError::InvalidPenaltyWallet);
+        require!(resolution_fee_bps <= MAX_RESOLUTION_FEE_BPS, EscrowError::ResolutionFeeTooHigh);
+
+        let config = &mut ctx.accounts.config;
+
+        config.admin = ctx.accounts.admin.key();
+        config.penalty_wallet = penalty_wallet;
+        config.bump = ctx.bumps.config;
+        config.resolution_fee_bps = resolution_fee_bps;
+        config.paused = false;
+        config.payouts_paused = false;
+
+        ctx.accounts.treasury.bump = ctx.bumps.treasury;
+
+        emit!(ConfigInitialized {
+            admin: config.admin,
+            penalty_wallet,
+            resolution_fee_bps,
+        });
+
+        Ok(())
+    }
+
+    /// Set resolution fee (config admin action)
+    pub fn set_resolution_fee(ctx: Context<UpdateConfig>, resolution_fee_bps: u16) -> Result<()> {
+        require!(resolution_fee_bps <= MAX_RESOLUTION_FEE_BPS, EscrowError::ResolutionFeeTooHigh);
+
+        let config = &mut ctx.accounts.config;
+
+        let previous_fee_bps = config.resolution_fee_bps;
+        config.resolution_fee_bps = resolution_fee_bps;
+
+        emit!(ResolutionFeeUpdated {
+            previous_fee_bps,
+            new_fee_bps: resolution_fee_bps,
+        });
+
+        Ok(())
+    }
+
+    /// Set paused (config admin action - emergency kill switch)
+    /// Pausing blocks new escrows and stakes, pause_payouts also blocks lessor payouts
+    pub fn set_paused(ctx: Context<UpdateConfig>, paused: bool, pause_payouts: bool) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+
+        config.paused = paused;
+        config.payouts_paused = paused && pause_payouts;
+
+        msg!("Program paused: {}, payouts paused: {}", config.paused, config.payouts_paused);
+
+        emit!(PauseUpdated {
+            paused: config.paused,
+            payouts_paused: config.payouts_paused,
+            admin: ctx.accounts.admin.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Withdraw treasury (config admin action - collect protocol fees)
+    /// The treasury PDA always keeps its rent-exempt minimum
+    /// Passing a mint and token accounts withdraws fees collected from token escrows instead
+    pub fn withdraw_treasury(ctx: Context<WithdrawTreasury>, amount: u64) -> Result<()> {
+        require!(amount > 0, EscrowError::InvalidAmount);
+
+        let treasury_info = ctx.accounts.treasury.to_account_info();
+
+        let mint = if let Some(treasury_token_account) = ctx.accounts.treasury_token_account.as_deref() {
+            let mint = ctx.accounts.mint.as_deref().ok_or(EscrowError::MissingTokenAccount)?;
+            let recipient_token_account = ctx.accounts.recipient_token_account.as_deref()
+                .ok_or(EscrowError::MissingTokenAccount)?;
+            let token_program = ctx.accounts.token_program.as_ref()
+                .ok_or(EscrowError::MissingTokenAccount)?;
+
+            require!(treasury_token_account.owner == treasury_info.key(), EscrowError::InvalidPayoutAccount);
+            require!(treasury_token_account.mint == mint.key(), EscrowError::InvalidStakeMint);
+            require!(amount <= treasury_token_account.amount, EscrowError::InsufficientFunds);
+
+            let treasury_seeds: &[&[u8]] = &[b"treasury", &[ctx.accounts.treasury.bump]];
+            let signer_seeds = [treasury_seeds];
+            let cpi_context = CpiContext::new_with_signer(
+                token_program.to_account_info(),
+                TransferChecked {
+                    from: treasury_token_account.to_account_info(),
+                    mint: mint.to_account_info(),
+                    to: recipient_token_account.to_account_info(),
+                    authority: treasury_info.clone(),
+                },
+                &signer_seeds,
+            );
+            token_interface::transfer_checked(cpi_context, amount, mint.decimals)?;
+
+            Some(mint.key())
+        } else {
+            let rent_exempt_minimum = Rent::get()?.minimum_balance(treasury_info.data_len());
+            let available = treasury_info
+                .lamports()
+                .saturating_sub(rent_exempt_minimum);
+            require!(amount <= available, EscrowError::InsufficientFunds);
+
+            transfer_from_escrow(&treasury_info, &ctx.accounts.recipient, amount)?;
+
+            None
+        };
+
+        emit!(TreasuryWithdrawn {
+            recipient: ctx.accounts.recipient.key(),
+            amount,
+            mint,
+        });
+
+        Ok(())
+    }
+
+    /// Set penalty wallet (config admin action - where the penalty wallet share of slashes is paid)
+    pub fn set_penalty_wallet(ctx: Context<UpdateConfig>, new_penalty_wallet: Pubkey) -> Result<()> {
+        require!(new_penalty_wallet != Pubkey::default(), EscrowError::InvalidPenaltyWallet);
+
+        let config = &mut ctx.accounts.config;
+
+        let previous_penalty_wallet = config.penalty_wallet;
+        config.penalty_wallet = new_penalty_wallet;
+
+        emit!(PenaltyWalletUpdated {
+            previous_penalty_wallet,
+            new_penalty_wallet,
+        });
+
+        Ok(())
+    }
+
+    /// Set config admin (config admin action - hand over protocol administration)
+    pub fn set_config_admin(ctx: Context<UpdateConfig>, new_admin: Pubkey) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+
+        let previous_admin = config.admin;
+        config.admin = new_admin;
+
+        emit!(ConfigAdminUpdated {
+            previous_admin,
+            new_admin,
+        });
+
+        Ok(())
+    }
+
+    /// Create arbiter panel (any authority - M-of-N panel that rules on disputes)
+    pub fn create_arbiter_panel(
+        ctx: Context<CreateArbiterPanel>,
+        arbiters: Vec<Pubkey>,
+        threshold: u8,
+    ) -> Result<()> {
+        require!(
+            !arbiters.is_empty() && arbiters.len() <= MAX_ARBITERS,
+            EscrowError::InvalidArbiterPanel
+        );
+        require!(
+            threshold > 0 && threshold as usize <= arbiters.len(),
+            EscrowError::InvalidArbiterPanel
+        );
+        require!(
+            arbiters.iter().enumerate().all(|(i, arbiter)| !arbiters[..i].contains(arbiter)),
+            EscrowError::InvalidArbiterPanel
+        );
+
+        let arbiter_panel = &mut ctx.accounts.arbiter_panel;
+        arbiter_panel.authority = ctx.accounts.authority.key();
+        arbiter_panel.arbiters = arbiters.clone();
+        arbiter_panel.threshold = threshold;
+        arbiter_panel.bump = ctx.bumps.arbiter_panel;
+        arbiter_panel.approved = false;
+
+        emit!(ArbiterPanelCreated {
+            arbiter_panel: arbiter_panel.key(),
+            authority: ctx.accounts.authority.key(),
+            arbiters,
+            threshold,
+        });
+
+        Ok(())
+    }
+
+    /// Set arbiter panel approval (config admin action - vet panels before escrows can use them)
+    pub fn set_arbiter_panel_approval(ctx: Context<SetArbiterPanelApproval>, approved: bool) -> Result<()> {
+        let arbiter_panel = &mut ctx.accounts.arbiter_panel;
+        arbiter_panel.approved = approved;
+
+        emit!(ArbiterPanelApprovalUpdated {
+            arbiter_panel: arbiter_panel.key(),
+            approved,
+            admin: ctx.accounts.admin.key(),
+        });
+
+        Ok(())
+    }
+
     /// Initialize apartment escrow with apartment owner
+    /// stake_discount lowers the required stake of SOL escrows for tenants with a clean reputation
+    #[allow(clippy::too_many_arguments)]
     pub fn initialize_apartment(
         ctx: Context<InitializeApartment>,
         apartment_hash: [u8; 32],
         apartment_id: String,
         apartment_owner: Pubkey,
+        decision_window: i64,
+        required_stake: u64,
+        referral_reward: u64,
+        referral_limit: u32,
+        dispute_window: i64,
+        slash_split: SlashSplit,
+        stake_discount: StakeDiscount,
     ) -> Result<()> {
+        require_not_paused(&ctx.accounts.config)?;
+
+        require!(apartment_hash == hash_string(&apartment_id), EscrowError::InvalidApartment);
+        require!(decision_window > 0, EscrowError::InvalidDecisionWindow);
+        require!(dispute_window > 0, EscrowError::InvalidDisputeWindow);
+        require_valid_slash_split(&slash_split)?;
+        require!(required_stake > 0, EscrowError::InvalidAmount);
+        // Referral reward is paid out of the stake, so it can never exceed it, even fully discounted
+        require!(
+            stake_discount.max_discount_bps as u64 <= BPS_DENOMINATOR,
+            EscrowError::InvalidBasisPoints
+        );
+        let minimum_stake = required_stake
+            .checked_sub(apply_bps(required_stake, stake_discount.max_discount_bps)?)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+        require!(referral_reward <= minimum_stake, EscrowError::InvalidStakeTerms);
+
+        // Token escrows get their vault created alongside the escrow
+        let stake_mint = ctx.accounts.stake_mint.as_ref().map(|mint| mint.key());
+        require!(
+            stake_mint.is_some() == ctx.accounts.stake_vault.is_some(),
+            EscrowError::MissingTokenAccount
+        );
+
         let escrow_account = &mut ctx.accounts.escrow_account;
 
+        // Lessor must resolve or slash every stake before this timestamp
+        let decision_deadline = Clock::get()?
+            .unix_timestamp
+            .checked_add(decision_window)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+
         escrow_account.apartment_id = apartment_id.clone();
         escrow_account.lessor = apartment_owner;
         escrow_account.total_staked = 0;
-        escrow_account.is_active = true;
+        escrow_account.status = EscrowStatus::Open;
         escrow_account.bump = ctx.bumps.escrow_account;
+        escrow_account.version = ESCROW_LAYOUT_VERSION;
+        escrow_account.decision_deadline = decision_deadline;
+        escrow_account.active_stakes = 0;
+        escrow_account.rent_payer = ctx.accounts.initializer.key();
+        escrow_account.pending_lessor = None;
+        escrow_account.required_stake = required_stake;
+        escrow_account.referral_reward = referral_reward;
+        escrow_account.referral_limit = referral_limit;
+        escrow_account.approved_profile = None;
+        escrow_account.arbiter_panel = ctx.accounts.arbiter_panel.key();
+        escrow_account.dispute_window = dispute_window;
+        escrow_account.pending_slashes = 0;
+        escrow_account.slash_split = slash_split;
+        escrow_account.stake_mint = stake_mint;
+        escrow_account.stake_discount = stake_discount;
+        escrow_account.lease_started_at = 0;
 
         emit!(EscrowInitialized {
             apartment_id,
             apartment_owner,
+            decision_deadline,
+            required_stake,
+            referral_reward,
+            referral_limit,
+            arbiter_panel: ctx.accounts.arbiter_panel.key(),
+            dispute_window,
+            slash_split,
+            stake_mint,
+            stake_discount,
         });
 
         Ok(())
     }
 
-    /// Stake SOL for a specific apartment (anyone can stake)
-    /// Escrow must be initialized first
+    /// Stake for a specific apartment (anyone can stake)
+    /// Escrow must be initialized first, stakes are paid in SOL or the escrow's stake mint
+    #[allow(unused_variables)]
     pub fn stake_for_apartment(
         ctx: Context<StakeForApartment>,
         apartment_hash: [u8; 32],
@@ -54,42 +703,94 @@ pub mod escrow {
         apartment_id: String,
         tenant_profile_id: String,
     ) -> Result<()> {
+        require_not_paused(&ctx.accounts.config)?;
+
         require!(amount > 0, EscrowError::InvalidAmount);
         require!(!apartment_id.is_empty(), EscrowError::InvalidApartment);
         require!(!tenant_profile_id.is_empty(), EscrowError::InvalidTenant);
+        require!(profile_hash == hash_string(&tenant_profile_id), EscrowError::InvalidTenant);
 
         let escrow_account = &mut ctx.accounts.escrow_account;
         let stake_record = &mut ctx.accounts.stake_record;
 
-        // Require escrow to be already initialized
-        require!(escrow_account.is_active, EscrowError::EscrowNotActive);
+        // Stakes are only taken while the listing is open
+        require_escrow_status(escrow_account, &[EscrowStatus::Open])?;
         require!(escrow_account.apartment_id == apartment_id, EscrowError::InvalidApartment);
+        require!(escrow_account.approved_profile.is_none(), EscrowError::TenantAlreadyApproved);
 
         // Simple key-value mapping: [profile_id + apartment_id] -> money_deposited
-        // Initialize or add to existing stake record
-        stake_record.tenant_profile_id = tenant_profile_id.clone();
-        stake_record.apartment_id = apartment_id.clone();
-        stake_record.staker = ctx.accounts.staker.key();
+        if stake_record.status == StakeStatus::Active {
+            // Top-ups never change who owns the stake, withdrawals and refunds pay that wallet
+            require!(ctx.accounts.staker.key() == stake_record.staker, EscrowError::UnauthorizedStaker);
+            require!(stake_record.version == STAKE_RECORD_LAYOUT_VERSION, EscrowError::StakeRecordNotMigrated);
+        } else {
+            // Count the record as live only when it (re)activates, not on top-ups
+            escrow_account.active_stakes = escrow_account.active_stakes
+                .checked_add(1)
+                .ok_or(EscrowError::ArithmeticOverflow)?;
+
+            stake_record.tenant_profile_id = tenant_profile_id.clone();
+            stake_record.apartment_id = apartment_id.clone();
+            stake_record.staker = ctx.accounts.staker.key();
+        }
+
         stake_record.amount = stake_record.amount
             .checked_add(amount)
             .ok_or(EscrowError::ArithmeticOverflow)?; // Add to existing if any
-        stake_record.is_active = true;
+        let required_stake = discounted_required_stake(escrow_account, ctx.accounts.reputation.as_deref())?;
+        require!(stake_record.amount >= required_stake, EscrowError::StakeBelowMinimum);
+        stake_record.status = StakeStatus::Active;
         stake_record.bump = ctx.bumps.stake_record;
+        stake_record.version = STAKE_RECORD_LAYOUT_VERSION;
 
         // Update total staked in escrow
         escrow_account.total_staked = escrow_account.total_staked
             .checked_add(amount)
             .ok_or(EscrowError::ArithmeticOverflow)?;
 
-        // Transfer SOL from staker to escrow account (PDA)
-        let cpi_context = CpiContext::new(
-            ctx.accounts.system_program.to_account_info(),
-            system_program::Transfer {
-                from: ctx.accounts.staker.to_account_info(),
-                to: ctx.accounts.escrow_account.to_account_info(),
-            },
-        );
-        system_program::transfer(cpi_context, amount)?;
+        let stake_vault = load_stake_vault(
+            &ctx.accounts.escrow_account,
+            ctx.accounts.stake_mint.as_deref(),
+            ctx.accounts.stake_vault.as_deref(),
+            ctx.accounts.token_program.as_ref(),
+        )?;
+
+        if let Some(stake_vault) = stake_vault {
+            // Transfer tokens from staker to the escrow's vault
+            let staker_token_account = ctx.accounts.staker_token_account.as_deref()
+                .ok_or(EscrowError::MissingTokenAccount)?;
+            let vault_balance = stake_vault.vault.amount;
+
+            let cpi_context = CpiContext::new(
+                stake_vault.token_program.to_account_info(),
+                TransferChecked {
+                    from: staker_token_account.to_account_info(),
+                    mint: stake_vault.mint.to_account_info(),
+                    to: stake_vault.vault.to_account_info(),
+                    authority: ctx.accounts.staker.to_account_info(),
+                },
+            );
+            token_interface::transfer_checked(cpi_context, amount, stake_vault.mint.decimals)?;
+
+            // Token-2022 transfer fees would leave the vault short of the recorded stake
+            let stake_vault = ctx.accounts.stake_vault.as_deref_mut()
+                .ok_or(EscrowError::MissingTokenAccount)?;
+            stake_vault.reload()?;
+            let received = stake_vault.amount
+                .checked_sub(vault_balance)
+                .ok_or(EscrowError::ArithmeticOverflow)?;
+            require!(received == amount, EscrowError::TransferFeeNotSupported);
+        } else {
+            // Transfer SOL from staker to escrow account (PDA)
+            let cpi_context = CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.staker.to_account_info(),
+                    to: ctx.accounts.escrow_account.to_account_info(),
+                },
+            );
+            system_program::transfer(cpi_context, amount)?;
+        }
 
         emit!(StakeCreated {
             tenant_profile_id,
@@ -101,252 +802,2839 @@ pub mod escrow {
         Ok(())
     }
 
-    /// Slash stake (lessor action - tenant broke terms)
-    pub fn slash_stake(
-        ctx: Context<SlashStake>,
+    /// Approve tenant (lessor action - listing awarded to a profile)
+    /// Every other applicant can withdraw their stake from this point on
+    #[allow(unused_variables)]
+    pub fn approve_tenant(
+        ctx: Context<ApproveTenant>,
         apartment_hash: [u8; 32],
         profile_hash: [u8; 32],
         apartment_id: String,
         tenant_profile_id: String,
-        apartment_owner: Pubkey,
     ) -> Result<()> {
         let escrow_account = &mut ctx.accounts.escrow_account;
-        let stake_record = &mut ctx.accounts.stake_record;
+        let stake_record = &ctx.accounts.stake_record;
 
-        // Debug logging - show all current values
-        msg!("=== SLASH STAKE DEBUG ===");
-        msg!("Apartment ID: {}", apartment_id);
-        msg!("Tenant Profile ID: {}", tenant_profile_id);
-        msg!("Apartment Owner: {}", apartment_owner);
-        msg!("Lessor (signer): {}", ctx.accounts.lessor.key());
-        msg!("Stake Record - Amount: {}", stake_record.amount);
-        msg!("Stake Record - Is Active: {}", stake_record.is_active);
-        msg!("Stake Record - Apartment ID: {}", stake_record.apartment_id);
-        msg!("Stake Record - Tenant Profile ID: {}", stake_record.tenant_profile_id);
-        msg!("Stake Record - Staker: {}", stake_record.staker);
-        msg!("Escrow - Total Staked: {}", escrow_account.total_staked);
-        msg!("Escrow - Apartment ID: {}", escrow_account.apartment_id);
-        msg!("Escrow - Lessor: {}", escrow_account.lessor);
-        msg!("Escrow - Is Active: {}", escrow_account.is_active);
-
-        require!(stake_record.is_active, EscrowError::StakeNotActive);
-        require!(ctx.accounts.lessor.key() == apartment_owner, EscrowError::UnauthorizedLessor);
+        require_escrow_status(escrow_account, &[EscrowStatus::Open])?;
+        require!(escrow_account.apartment_id == apartment_id, EscrowError::InvalidApartment);
+        require!(ctx.accounts.lessor.key() == escrow_account.lessor, EscrowError::UnauthorizedLessor);
+        require!(escrow_account.approved_profile.is_none(), EscrowError::TenantAlreadyApproved);
+        require!(stake_record.status == StakeStatus::Active, EscrowError::StakeNotActive);
         require!(stake_record.apartment_id == apartment_id, EscrowError::InvalidApartment);
         require!(stake_record.tenant_profile_id == tenant_profile_id, EscrowError::InvalidTenant);
 
-        let stake_record_amount = stake_record.amount;
-        let escrow_total_staked = escrow_account.total_staked;
-        let staker = stake_record.staker;
-        
-        // Use the minimum of what the stake record claims and what's available in escrow
-        let transfer_amount = std::cmp::min(stake_record_amount, escrow_total_staked);
-        
-        msg!("Stake record claims: {}", stake_record_amount);
-        msg!("Escrow has available: {}", escrow_total_staked);
-        msg!("Will transfer to penalty wallet: {}", transfer_amount);
-        
-        // Check if we have anything to transfer
-        if transfer_amount == 0 {
-            msg!("ERROR: No funds available to transfer!");
-            return Err(EscrowError::InsufficientFunds.into());
-        }
+        escrow_account.approved_profile = Some(profile_hash);
+        transition_escrow(escrow_account, EscrowStatus::TenantApproved);
 
-        stake_record.is_active = false;
-        
-        // Update stake record to reflect what was actually transferred
-        stake_record.amount = stake_record_amount
-            .checked_sub(transfer_amount)
-            .ok_or(EscrowError::ArithmeticOverflow)?;
+        msg!("Approved tenant {} for apartment {}", tenant_profile_id, apartment_id);
 
-        // Update total staked in escrow
-        escrow_account.total_staked = escrow_total_staked
-            .checked_sub(transfer_amount)
-            .ok_or(EscrowError::InsufficientFunds)?;
+        emit!(TenantApproved {
+            tenant_profile_id,
+            apartment_id,
+            profile_hash,
+            staker: stake_record.staker,
+        });
 
-        msg!("Successfully updated total_staked to: {}", escrow_account.total_staked);
-        msg!("Stake record updated to: {}", stake_record.amount);
+        Ok(())
+    }
 
-        // Get the hardcoded penalty wallet
-        let penalty_wallet_pubkey = get_penalty_wallet();
-        msg!("Transferring {} to penalty wallet: {}", transfer_amount, penalty_wallet_pubkey);
+    /// Start lease (lessor action - approved tenant signed the lease)
+    #[allow(unused_variables)]
+    pub fn start_lease(
+        ctx: Context<UpdateLeasePhase>,
+        apartment_hash: [u8; 32],
+        apartment_id: String,
+    ) -> Result<()> {
+        let escrow_account = &mut ctx.accounts.escrow_account;
 
-        // Transfer SOL directly to penalty wallet
-        **ctx.accounts.escrow_account.to_account_info().try_borrow_mut_lamports()? -= transfer_amount;
-        **ctx.accounts.penalty_wallet.to_account_info().try_borrow_mut_lamports()? += transfer_amount;
+        require_escrow_status(escrow_account, &[EscrowStatus::TenantApproved])?;
+        require!(escrow_account.apartment_id == apartment_id, EscrowError::InvalidApartment);
+        require!(ctx.accounts.lessor.key() == escrow_account.lessor, EscrowError::UnauthorizedLessor);
 
-        msg!("Slashed funds transferred to penalty wallet successfully");
+        escrow_account.lease_started_at = Clock::get()?.unix_timestamp;
+        transition_escrow(escrow_account, EscrowStatus::LeaseActive);
 
-        emit!(StakeSlashed {
-            tenant_profile_id,
-            apartment_id,
-            staker,
-            amount: transfer_amount,
-        });
+        Ok(())
+    }
+
+    /// Begin move-out (lessor action - lease ended, tenant moving out)
+    /// The approved tenant's stake must already be a deposit, nothing else settles in move-out review
+    #[allow(unused_variables)]
+    pub fn begin_move_out(
+        ctx: Context<BeginMoveOut>,
+        apartment_hash: [u8; 32],
+        apartment_id: String,
+        profile_hash: [u8; 32],
+    ) -> Result<()> {
+        let escrow_account = &mut ctx.accounts.escrow_account;
+
+        require_escrow_status(escrow_account, &[EscrowStatus::LeaseActive])?;
+        require!(escrow_account.apartment_id == apartment_id, EscrowError::InvalidApartment);
+        require!(ctx.accounts.lessor.key() == escrow_account.lessor, EscrowError::UnauthorizedLessor);
+        require!(escrow_account.approved_profile == Some(profile_hash), EscrowError::NotApprovedTenant);
+        require!(ctx.accounts.stake_record.status == StakeStatus::Deposit, EscrowError::StakeNotDeposit);
+
+        transition_escrow(escrow_account, EscrowStatus::MoveOutReview);
 
         Ok(())
     }
 
-    /// Resolve stake (lessor action - tenant fulfilled terms)
-    pub fn resolve_stake(
-        ctx: Context<ResolveStake>,
+    /// Convert to deposit (lessor action - approved tenant's stake becomes the lease security deposit)
+    /// The deposit is released through move-out settlement, which the lessor must propose within
+    /// one dispute window after lease_end or the tenant gets the deposit back in full
+    #[allow(unused_variables)]
+    pub fn convert_to_deposit(
+        ctx: Context<ConvertToDeposit>,
         apartment_hash: [u8; 32],
         profile_hash: [u8; 32],
         apartment_id: String,
         tenant_profile_id: String,
-        apartment_owner: Pubkey,
-        referrer_pubkey: Option<Pubkey>,
-        reward_amount: u64,
+        lease_end: i64,
     ) -> Result<()> {
-        let escrow_account = &mut ctx.accounts.escrow_account;
+        let escrow_account = &ctx.accounts.escrow_account;
         let stake_record = &mut ctx.accounts.stake_record;
 
-        // Debug logging - show all current values
-        msg!("=== RESOLVE STAKE DEBUG ===");
-        msg!("Apartment ID: {}", apartment_id);
-        msg!("Tenant Profile ID: {}", tenant_profile_id);
-        msg!("Apartment Owner: {}", apartment_owner);
-        msg!("Lessor (signer): {}", ctx.accounts.lessor.key());
-        msg!("Referrer Pubkey: {:?}", referrer_pubkey);
-        msg!("Reward Amount: {}", reward_amount);
-        msg!("Stake Record - Amount: {}", stake_record.amount);
-        msg!("Stake Record - Is Active: {}", stake_record.is_active);
-        msg!("Stake Record - Apartment ID: {}", stake_record.apartment_id);
-        msg!("Stake Record - Tenant Profile ID: {}", stake_record.tenant_profile_id);
-        msg!("Stake Record - Staker: {}", stake_record.staker);
-        msg!("Escrow - Total Staked: {}", escrow_account.total_staked);
-        msg!("Escrow - Apartment ID: {}", escrow_account.apartment_id);
-        msg!("Escrow - Lessor: {}", escrow_account.lessor);
-        msg!("Escrow - Is Active: {}", escrow_account.is_active);
-
-        require!(stake_record.is_active, EscrowError::StakeNotActive);
-        require!(ctx.accounts.lessor.key() == apartment_owner, EscrowError::UnauthorizedLessor);
+        require_escrow_status(escrow_account, &[EscrowStatus::LeaseActive])?;
+        require!(escrow_account.apartment_id == apartment_id, EscrowError::InvalidApartment);
+        require!(ctx.accounts.lessor.key() == escrow_account.lessor, EscrowError::UnauthorizedLessor);
+        require!(escrow_account.approved_profile == Some(profile_hash), EscrowError::NotApprovedTenant);
+        require!(stake_record.status == StakeStatus::Active, EscrowError::StakeNotActive);
         require!(stake_record.apartment_id == apartment_id, EscrowError::InvalidApartment);
         require!(stake_record.tenant_profile_id == tenant_profile_id, EscrowError::InvalidTenant);
+        require!(lease_end > Clock::get()?.unix_timestamp, EscrowError::InvalidLeaseEnd);
 
-        let stake_record_amount = stake_record.amount;
-        let escrow_total_staked = escrow_account.total_staked;
-        let staker = stake_record.staker;
-        
-        // Use the minimum of what the stake record claims and what's available in escrow
-        let total_transfer_amount = std::cmp::min(stake_record_amount, escrow_total_staked);
-        
-        msg!("Stake record claims: {}", stake_record_amount);
-        msg!("Escrow has available: {}", escrow_total_staked);
-        msg!("Total transfer amount: {}", total_transfer_amount);
-        
-        // Check if we have anything to transfer
-        if total_transfer_amount == 0 {
-            msg!("ERROR: No funds available to transfer!");
-            return Err(EscrowError::InsufficientFunds.into());
+        stake_record.status = StakeStatus::Deposit;
+        stake_record.lease_end = lease_end;
+
+        msg!("Converted stake of {} to a security deposit for {}", stake_record.amount, tenant_profile_id);
+
+        emit!(StakeConvertedToDeposit {
+            tenant_profile_id,
+            apartment_id,
+            staker: stake_record.staker,
+            amount: stake_record.amount,
+            lease_end,
+        });
+
+        Ok(())
+    }
+
+    /// Settle move-out (lessor action - propose itemized deductions from the deposit)
+    /// The tenant has the escrow's dispute window to accept before the deductions apply anyway
+    #[allow(unused_variables)]
+    pub fn settle_move_out(
+        ctx: Context<SettleMoveOut>,
+        apartment_hash: [u8; 32],
+        profile_hash: [u8; 32],
+        deductions: Vec<DeductionItem>,
+    ) -> Result<()> {
+        let escrow_account = &ctx.accounts.escrow_account;
+        let stake_record = &ctx.accounts.stake_record;
+
+        require_escrow_status(escrow_account, &[EscrowStatus::MoveOutReview])?;
+        require!(ctx.accounts.lessor.key() == escrow_account.lessor, EscrowError::UnauthorizedLessor);
+        require!(escrow_account.approved_profile == Some(profile_hash), EscrowError::NotApprovedTenant);
+        require!(stake_record.status == StakeStatus::Deposit, EscrowError::StakeNotDeposit);
+
+        let total_deductions = total_deductions(&deductions)?;
+        require!(total_deductions <= stake_record.amount, EscrowError::DeductionsExceedDeposit);
+
+        let now = Clock::get()?.unix_timestamp;
+        require!(now <= move_out_deadline(escrow_account, stake_record)?, EscrowError::MoveOutWindowClosed);
+
+        let response_deadline = now
+            .checked_add(escrow_account.dispute_window)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+
+        let move_out_settlement = &mut ctx.accounts.move_out_settlement;
+        move_out_settlement.tenant_profile_id = stake_record.tenant_profile_id.clone();
+        move_out_settlement.apartment_id = stake_record.apartment_id.clone();
+        move_out_settlement.staker = stake_record.staker;
+        move_out_settlement.deposit_amount = stake_record.amount;
+        move_out_settlement.total_deductions = total_deductions;
+        move_out_settlement.response_deadline = response_deadline;
+        move_out_settlement.rent_payer = ctx.accounts.lessor.key();
+        move_out_settlement.bump = ctx.bumps.move_out_settlement;
+        move_out_settlement.deductions = deductions.clone();
+
+        msg!(
+            "Proposed {} in deductions from a {} deposit, tenant can respond until {}",
+            total_deductions,
+            stake_record.amount,
+            response_deadline
+        );
+
+        emit!(MoveOutProposed {
+            tenant_profile_id: stake_record.tenant_profile_id.clone(),
+            apartment_id: stake_record.apartment_id.clone(),
+            staker: stake_record.staker,
+            deposit_amount: stake_record.amount,
+            total_deductions,
+            deductions,
+            response_deadline,
+        });
+
+        Ok(())
+    }
+
+    /// Release deposit (tenant accepts the deductions, or anyone once the response window lapses)
+    /// Deductions go to the lessor and the rest of the deposit back to the tenant
+    /// Counts as a completed lease on the tenant's reputation
+    #[allow(unused_variables)]
+    pub fn release_deposit(
+        ctx: Context<ReleaseDeposit>,
+        apartment_hash: [u8; 32],
+        profile_hash: [u8; 32],
+    ) -> Result<()> {
+        let move_out_settlement = &ctx.accounts.move_out_settlement;
+        let accepted_by_tenant = ctx.accounts.caller.key() == move_out_settlement.staker;
+
+        // Tenant self-service stays open during a payout pause, lapsed settlements do not
+        if !accepted_by_tenant {
+            require_payouts_allowed(&ctx.accounts.config)?;
+
+            let now = Clock::get()?.unix_timestamp;
+            require!(now > move_out_settlement.response_deadline, EscrowError::ResponseWindowOpen);
         }
 
-        // Calculate referrer reward and remaining amount for staker
-        let referrer_reward = if referrer_pubkey.is_some() && reward_amount > 0 {
-            std::cmp::min(reward_amount, total_transfer_amount)
-        } else {
-            0
+        let escrow_account = &mut ctx.accounts.escrow_account;
+        let stake_record = &mut ctx.accounts.stake_record;
+
+        require!(stake_record.status == StakeStatus::Deposit, EscrowError::StakeNotDeposit);
+
+        let deposit_amount = stake_record.amount;
+        let deducted_amount = move_out_settlement.total_deductions;
+        let refunded_amount = deposit_amount
+            .checked_sub(deducted_amount)
+            .ok_or(EscrowError::DeductionsExceedDeposit)?;
+
+        stake_record.status = StakeStatus::Settled;
+        stake_record.amount = 0;
+        escrow_account.active_stakes = escrow_account.active_stakes
+            .checked_sub(1)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+        end_listing(escrow_account);
+        escrow_account.total_staked = escrow_account.total_staked
+            .checked_sub(deposit_amount)
+            .ok_or(EscrowError::InsufficientFunds)?;
+
+        let stake_vault = load_stake_vault(
+            &ctx.accounts.escrow_account,
+            ctx.accounts.stake_mint.as_deref(),
+            ctx.accounts.stake_vault.as_deref(),
+            ctx.accounts.token_program.as_ref(),
+        )?;
+        let lessor_recipient = PayoutRecipient {
+            wallet: ctx.accounts.escrow_account.lessor,
+            wallet_info: Some(&ctx.accounts.lessor),
+            token_account: ctx.accounts.lessor_token_account.as_deref(),
+        };
+        pay_from_escrow(
+            &ctx.accounts.escrow_account,
+            &apartment_hash,
+            stake_vault.as_ref(),
+            &lessor_recipient,
+            deducted_amount,
+        )?;
+        let staker_recipient = PayoutRecipient {
+            wallet: move_out_settlement.staker,
+            wallet_info: Some(&ctx.accounts.staker),
+            token_account: ctx.accounts.staker_token_account.as_deref(),
+        };
+        pay_from_escrow(
+            &ctx.accounts.escrow_account,
+            &apartment_hash,
+            stake_vault.as_ref(),
+            &staker_recipient,
+            refunded_amount,
+        )?;
+
+        msg!("Released deposit - deducted: {}, refunded: {}", deducted_amount, refunded_amount);
+
+        // Returning the deposit completes the approved tenant's lease, for the part they got back
+        let reputation = &mut ctx.accounts.reputation;
+        reputation.profile_hash = profile_hash;
+        reputation.owner = move_out_settlement.staker;
+        reputation.bump = ctx.bumps.reputation;
+        if refunded_amount > 0 {
+            record_completed_stake(reputation, &ctx.accounts.escrow_account, refunded_amount)?;
+        }
+
+        emit!(DepositReleased {
+            tenant_profile_id: move_out_settlement.tenant_profile_id.clone(),
+            apartment_id: move_out_settlement.apartment_id.clone(),
+            staker: move_out_settlement.staker,
+            deducted_amount,
+            refunded_amount,
+            accepted_by_tenant,
+        });
+
+        Ok(())
+    }
+
+    /// Refund unsettled deposit (permissionless - lessor proposed no move-out settlement in time)
+    /// Returns the full deposit to the tenant once a dispute window has passed after the lease end
+    #[allow(unused_variables)]
+    pub fn refund_unsettled_deposit(
+        ctx: Context<RefundUnsettledDeposit>,
+        apartment_hash: [u8; 32],
+        profile_hash: [u8; 32],
+    ) -> Result<()> {
+        let escrow_account = &mut ctx.accounts.escrow_account;
+        let stake_record = &mut ctx.accounts.stake_record;
+
+        require!(stake_record.status == StakeStatus::Deposit, EscrowError::StakeNotDeposit);
+        require!(ctx.accounts.move_out_settlement.data_is_empty(), EscrowError::MoveOutPending);
+
+        let now = Clock::get()?.unix_timestamp;
+        require!(now > move_out_deadline(escrow_account, stake_record)?, EscrowError::MoveOutWindowOpen);
+
+        let refund_amount = stake_record.amount;
+
+        stake_record.status = StakeStatus::Settled;
+        stake_record.amount = 0;
+        escrow_account.active_stakes = escrow_account.active_stakes
+            .checked_sub(1)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+        end_listing(escrow_account);
+        escrow_account.total_staked = escrow_account.total_staked
+            .checked_sub(refund_amount)
+            .ok_or(EscrowError::InsufficientFunds)?;
+
+        let stake_vault = load_stake_vault(
+            &ctx.accounts.escrow_account,
+            ctx.accounts.stake_mint.as_deref(),
+            ctx.accounts.stake_vault.as_deref(),
+            ctx.accounts.token_program.as_ref(),
+        )?;
+        let staker_recipient = PayoutRecipient {
+            wallet: ctx.accounts.staker.key(),
+            wallet_info: Some(&ctx.accounts.staker),
+            token_account: ctx.accounts.staker_token_account.as_deref(),
+        };
+        pay_from_escrow(
+            &ctx.accounts.escrow_account,
+            &apartment_hash,
+            stake_vault.as_ref(),
+            &staker_recipient,
+            refund_amount,
+        )?;
+
+        msg!("Move-out deadline passed, refunded deposit of {} to staker: {}", refund_amount, ctx.accounts.staker.key());
+
+        let stake_record = &ctx.accounts.stake_record;
+        emit!(UnsettledDepositRefunded {
+            tenant_profile_id: stake_record.tenant_profile_id.clone(),
+            apartment_id: stake_record.apartment_id.clone(),
+            staker: stake_record.staker,
+            amount: refund_amount,
+            lease_end: stake_record.lease_end,
+        });
+
+        Ok(())
+    }
+
+    /// Slash stake (lessor action - tenant broke terms)
+    /// Slashes all or part of the stake and refunds the rest to the staker
+    /// Slashed funds are held until the dispute window closes or the arbiter panel rules
+    /// line_items, when given, must itemize the full slashed amount
+    #[allow(clippy::too_many_arguments)]
+    pub fn slash_stake(
+        ctx: Context<SlashStake>,
+        apartment_hash: [u8; 32],
+        profile_hash: [u8; 32],
+        apartment_id: String,
+        tenant_profile_id: String,
+        apartment_owner: Pubkey,
+        slash_amount: SlashAmount,
+        reason: SlashReason,
+        line_items: Vec<DeductionItem>,
+    ) -> Result<()> {
+        require_payouts_allowed(&ctx.accounts.config)?;
+
+        let escrow_account = &mut ctx.accounts.escrow_account;
+        let stake_record = &mut ctx.accounts.stake_record;
+
+        require_escrow_status(escrow_account, &[EscrowStatus::TenantApproved, EscrowStatus::LeaseActive])?;
+        require!(stake_record.status == StakeStatus::Active, EscrowError::StakeNotActive);
+        require!(ctx.accounts.lessor.key() == apartment_owner, EscrowError::UnauthorizedLessor);
+        require!(escrow_account.lessor == apartment_owner, EscrowError::UnauthorizedLessor);
+        require!(stake_record.apartment_id == apartment_id, EscrowError::InvalidApartment);
+        require!(stake_record.tenant_profile_id == tenant_profile_id, EscrowError::InvalidTenant);
+        require!(escrow_account.approved_profile == Some(profile_hash), EscrowError::NotApprovedTenant);
+
+        let stake_record_amount = stake_record.amount;
+        let escrow_total_staked = escrow_account.total_staked;
+        let staker = stake_record.staker;
+        
+        // Use the minimum of what the stake record claims and what's available in escrow
+        let transfer_amount = std::cmp::min(stake_record_amount, escrow_total_staked);
+        require!(transfer_amount > 0, EscrowError::InsufficientFunds);
+
+        msg!("Slashing {:?} of a {} stake for {} ({:?})", slash_amount, transfer_amount, tenant_profile_id, reason);
+
+        // Split the available stake into the slashed part and the staker refund
+        let slashed_amount = match slash_amount {
+            SlashAmount::Amount(amount) => amount,
+            SlashAmount::BasisPoints(bps) => apply_bps(transfer_amount, bps)?,
         };
+        require!(
+            slashed_amount > 0 && slashed_amount <= transfer_amount,
+            EscrowError::InvalidSlashAmount
+        );
+
+        let itemized_amount = total_deductions(&line_items)?;
+        require!(
+            line_items.is_empty() || itemized_amount == slashed_amount,
+            EscrowError::LineItemsMismatch
+        );
+
+        let refund_amount = transfer_amount
+            .checked_sub(slashed_amount)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+
+        stake_record.status = StakeStatus::Settled;
+        escrow_account.active_stakes = escrow_account.active_stakes
+            .checked_sub(1)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+        end_listing(escrow_account);
         
-        let staker_amount = total_transfer_amount
-            .checked_sub(referrer_reward)
+        // Update stake record to reflect what was actually transferred
+        stake_record.amount = stake_record_amount
+            .checked_sub(transfer_amount)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+
+        // Update total staked in escrow
+        escrow_account.total_staked = escrow_total_staked
+            .checked_sub(transfer_amount)
+            .ok_or(EscrowError::InsufficientFunds)?;
+
+        // Hold slashed funds in the escrow PDA until the dispute is settled
+        let dispute_deadline = Clock::get()?
+            .unix_timestamp
+            .checked_add(escrow_account.dispute_window)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+        // A disputed slash gets another dispute window for the arbiter panel to rule
+        let ruling_deadline = dispute_deadline
+            .checked_add(escrow_account.dispute_window)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+
+        escrow_account.pending_slashes = escrow_account.pending_slashes
+            .checked_add(1)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+
+        let slash_dispute = &mut ctx.accounts.slash_dispute;
+        slash_dispute.tenant_profile_id = tenant_profile_id.clone();
+        slash_dispute.apartment_id = apartment_id.clone();
+        slash_dispute.staker = staker;
+        slash_dispute.amount = slashed_amount;
+        slash_dispute.reason = reason;
+        slash_dispute.dispute_deadline = dispute_deadline;
+        slash_dispute.ruling_deadline = ruling_deadline;
+        slash_dispute.status = DisputeStatus::Pending;
+        slash_dispute.rent_payer = ctx.accounts.lessor.key();
+        slash_dispute.bump = ctx.bumps.slash_dispute;
+        slash_dispute.votes = Vec::new();
+        slash_dispute.line_items = line_items.clone();
+
+        msg!("Holding {} until dispute deadline: {}", slashed_amount, dispute_deadline);
+
+        // Report how the slash will be split if it goes through undisputed
+        let referrer_pubkey = load_referral(&ctx.accounts.referral, &staker)?
+            .map(|referral| referral.referrer);
+        let shares = split_penalty(slashed_amount, &escrow_account.slash_split, referrer_pubkey.is_some())?;
+
+        // Refund whatever was not slashed to the original staker
+        if refund_amount > 0 {
+            let stake_vault = load_stake_vault(
+                &ctx.accounts.escrow_account,
+                ctx.accounts.stake_mint.as_deref(),
+                ctx.accounts.stake_vault.as_deref(),
+                ctx.accounts.token_program.as_ref(),
+            )?;
+            let staker_recipient = PayoutRecipient {
+                wallet: staker,
+                wallet_info: Some(&ctx.accounts.staker),
+                token_account: ctx.accounts.staker_token_account.as_deref(),
+            };
+            pay_from_escrow(
+                &ctx.accounts.escrow_account,
+                &apartment_hash,
+                stake_vault.as_ref(),
+                &staker_recipient,
+                refund_amount,
+            )?;
+
+            msg!("Refunded {} to staker: {}", refund_amount, staker);
+        }
+
+        // The slash is recorded on the tenant's reputation only once it stands
+        let reputation = &mut ctx.accounts.reputation;
+        reputation.profile_hash = profile_hash;
+        reputation.owner = staker;
+        reputation.bump = ctx.bumps.reputation;
+
+        emit!(StakeSlashed {
+            tenant_profile_id,
+            apartment_id,
+            staker,
+            amount: slashed_amount,
+            refunded_amount: refund_amount,
+            reason,
+            dispute_deadline,
+            lessor_share: shares.lessor,
+            penalty_wallet_share: shares.penalty_wallet,
+            referrer_share: shares.referrer,
+            referrer: referrer_pubkey,
+            line_items,
+        });
+
+        Ok(())
+    }
+
+    /// Dispute slash (staker action - contest a slash within the dispute window)
+    #[allow(unused_variables)]
+    pub fn dispute_slash(
+        ctx: Context<DisputeSlash>,
+        apartment_hash: [u8; 32],
+        profile_hash: [u8; 32],
+    ) -> Result<()> {
+        let slash_dispute = &mut ctx.accounts.slash_dispute;
+
+        require!(slash_dispute.status == DisputeStatus::Pending, EscrowError::InvalidDisputeStatus);
+        require!(ctx.accounts.staker.key() == slash_dispute.staker, EscrowError::UnauthorizedStaker);
+
+        let now = Clock::get()?.unix_timestamp;
+        require!(now <= slash_dispute.dispute_deadline, EscrowError::DisputeWindowClosed);
+
+        slash_dispute.status = DisputeStatus::Disputed;
+
+        msg!("Slash of {} disputed by staker: {}", slash_dispute.amount, slash_dispute.staker);
+
+        emit!(SlashDisputed {
+            tenant_profile_id: slash_dispute.tenant_profile_id.clone(),
+            apartment_id: slash_dispute.apartment_id.clone(),
+            staker: slash_dispute.staker,
+            amount: slash_dispute.amount,
+            ruling_deadline: slash_dispute.ruling_deadline,
+        });
+
+        Ok(())
+    }
+
+    /// Vote on dispute (arbiter panel member action - settle a disputed slash)
+    /// tenant_share_bps of the held amount goes back to the staker, the rest is split like an undisputed slash
+    /// The ruling executes once `threshold` panel members vote for the same split before the ruling deadline
+    /// Arbiters can change their vote until then, and never rule on their own escrow or stake
+    /// A panel whose approval the config admin revoked cannot rule, its disputes refund through refund_unruled_dispute
+    #[allow(unused_variables)]
+    pub fn vote_on_dispute(
+        ctx: Context<VoteOnDispute>,
+        apartment_hash: [u8; 32],
+        profile_hash: [u8; 32],
+        tenant_share_bps: u16,
+    ) -> Result<()> {
+        require_payouts_allowed(&ctx.accounts.config)?;
+
+        require!(tenant_share_bps as u64 <= BPS_DENOMINATOR, EscrowError::InvalidBasisPoints);
+
+        let arbiter_panel = &ctx.accounts.arbiter_panel;
+        let arbiter = ctx.accounts.arbiter.key();
+        require!(arbiter_panel.arbiters.contains(&arbiter), EscrowError::UnauthorizedArbiter);
+        require!(arbiter != ctx.accounts.escrow_account.lessor, EscrowError::UnauthorizedArbiter);
+
+        let slash_dispute = &mut ctx.accounts.slash_dispute;
+        require!(slash_dispute.status == DisputeStatus::Disputed, EscrowError::InvalidDisputeStatus);
+        require!(arbiter != slash_dispute.staker, EscrowError::UnauthorizedArbiter);
+
+        let now = Clock::get()?.unix_timestamp;
+        require!(now <= slash_dispute.ruling_deadline, EscrowError::RulingWindowClosed);
+
+        // A changed vote replaces the arbiter's earlier one, so split votes can still converge
+        if let Some(vote) = slash_dispute.votes.iter_mut().find(|vote| vote.arbiter == arbiter) {
+            require!(vote.tenant_share_bps != tenant_share_bps, EscrowError::AlreadyVoted);
+            vote.tenant_share_bps = tenant_share_bps;
+        } else {
+            slash_dispute.votes.push(ArbiterVote {
+                arbiter,
+                tenant_share_bps,
+            });
+        }
+
+        let matching_votes = slash_dispute.votes
+            .iter()
+            .filter(|vote| vote.tenant_share_bps == tenant_share_bps)
+            .count() as u8;
+
+        emit!(DisputeVoteCast {
+            tenant_profile_id: slash_dispute.tenant_profile_id.clone(),
+            apartment_id: slash_dispute.apartment_id.clone(),
+            arbiter,
+            tenant_share_bps,
+            matching_votes,
+            threshold: arbiter_panel.threshold,
+        });
+
+        if matching_votes < arbiter_panel.threshold {
+            msg!("Vote recorded: {}/{} for {} bps", matching_votes, arbiter_panel.threshold, tenant_share_bps);
+            return Ok(());
+        }
+
+        // Threshold reached - execute the ruling
+        let held_amount = slash_dispute.amount;
+        let tenant_amount = apply_bps(held_amount, tenant_share_bps)?;
+        let penalty_amount = held_amount
+            .checked_sub(tenant_amount)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+
+        let escrow_account = &mut ctx.accounts.escrow_account;
+        escrow_account.pending_slashes = escrow_account.pending_slashes
+            .checked_sub(1)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+
+        let stake_vault = load_stake_vault(
+            &ctx.accounts.escrow_account,
+            ctx.accounts.stake_mint.as_deref(),
+            ctx.accounts.stake_vault.as_deref(),
+            ctx.accounts.token_program.as_ref(),
+        )?;
+
+        if tenant_amount > 0 {
+            let staker_recipient = PayoutRecipient {
+                wallet: ctx.accounts.slash_dispute.staker,
+                wallet_info: Some(&ctx.accounts.staker),
+                token_account: ctx.accounts.staker_token_account.as_deref(),
+            };
+            pay_from_escrow(
+                &ctx.accounts.escrow_account,
+                &apartment_hash,
+                stake_vault.as_ref(),
+                &staker_recipient,
+                tenant_amount,
+            )?;
+
+            msg!("Returned {} to staker: {}", tenant_amount, ctx.accounts.staker.key());
+        }
+
+        // Split whatever the staker does not get back
+        let referrer_pubkey = load_referral(&ctx.accounts.referral, &ctx.accounts.slash_dispute.staker)?
+            .map(|referral| referral.referrer);
+        let shares = split_penalty(
+            penalty_amount,
+            &ctx.accounts.escrow_account.slash_split,
+            referrer_pubkey.is_some(),
+        )?;
+        let referrer_recipient = referrer_pubkey.map(|referrer| PayoutRecipient {
+            wallet: referrer,
+            wallet_info: ctx.accounts.referrer.as_ref(),
+            token_account: ctx.accounts.referrer_token_account.as_deref(),
+        });
+        pay_penalty_shares(
+            &ctx.accounts.escrow_account,
+            &apartment_hash,
+            stake_vault.as_ref(),
+            &PayoutRecipient {
+                wallet: ctx.accounts.escrow_account.lessor,
+                wallet_info: Some(&ctx.accounts.lessor),
+                token_account: ctx.accounts.lessor_token_account.as_deref(),
+            },
+            &PayoutRecipient {
+                wallet: ctx.accounts.config.penalty_wallet,
+                wallet_info: Some(&ctx.accounts.penalty_wallet),
+                token_account: ctx.accounts.penalty_wallet_token_account.as_deref(),
+            },
+            referrer_recipient.as_ref(),
+            &shares,
+        )?;
+
+        // A ruling fully in the tenant's favour leaves their reputation untouched
+        if penalty_amount > 0 {
+            record_slash(&mut ctx.accounts.reputation, &ctx.accounts.escrow_account, penalty_amount)?;
+        }
+
+        let slash_dispute = &ctx.accounts.slash_dispute;
+        emit!(DisputeRuled {
+            tenant_profile_id: slash_dispute.tenant_profile_id.clone(),
+            apartment_id: slash_dispute.apartment_id.clone(),
+            staker: slash_dispute.staker,
+            arbiter_panel: ctx.accounts.arbiter_panel.key(),
+            tenant_amount,
+            penalty_amount,
+            lessor_amount: shares.lessor,
+            penalty_wallet_amount: shares.penalty_wallet,
+            referrer_amount: shares.referrer,
+            referrer: referrer_pubkey,
+        });
+
+        // Dispute is settled, return its rent to the lessor who paid for it
+        ctx.accounts.slash_dispute.close(ctx.accounts.rent_payer.to_account_info())?;
+
+        Ok(())
+    }
+
+    /// Finalize slash (permissionless - dispute window closed without a dispute)
+    /// Splits the held funds between the lessor, the config penalty wallet and referrer
+    #[allow(unused_variables)]
+    pub fn finalize_slash(
+        ctx: Context<FinalizeSlash>,
+        apartment_hash: [u8; 32],
+        profile_hash: [u8; 32],
+    ) -> Result<()> {
+        require_payouts_allowed(&ctx.accounts.config)?;
+
+        let escrow_account = &mut ctx.accounts.escrow_account;
+        let slash_dispute = &ctx.accounts.slash_dispute;
+
+        require!(slash_dispute.status == DisputeStatus::Pending, EscrowError::InvalidDisputeStatus);
+
+        let now = Clock::get()?.unix_timestamp;
+        require!(now > slash_dispute.dispute_deadline, EscrowError::DisputeWindowOpen);
+
+        let penalty_amount = slash_dispute.amount;
+
+        escrow_account.pending_slashes = escrow_account.pending_slashes
+            .checked_sub(1)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+
+        let referrer_pubkey = load_referral(&ctx.accounts.referral, &slash_dispute.staker)?
+            .map(|referral| referral.referrer);
+        let shares = split_penalty(penalty_amount, &escrow_account.slash_split, referrer_pubkey.is_some())?;
+
+        let stake_vault = load_stake_vault(
+            &ctx.accounts.escrow_account,
+            ctx.accounts.stake_mint.as_deref(),
+            ctx.accounts.stake_vault.as_deref(),
+            ctx.accounts.token_program.as_ref(),
+        )?;
+        let referrer_recipient = referrer_pubkey.map(|referrer| PayoutRecipient {
+            wallet: referrer,
+            wallet_info: ctx.accounts.referrer.as_ref(),
+            token_account: ctx.accounts.referrer_token_account.as_deref(),
+        });
+        pay_penalty_shares(
+            &ctx.accounts.escrow_account,
+            &apartment_hash,
+            stake_vault.as_ref(),
+            &PayoutRecipient {
+                wallet: ctx.accounts.escrow_account.lessor,
+                wallet_info: Some(&ctx.accounts.lessor),
+                token_account: ctx.accounts.lessor_token_account.as_deref(),
+            },
+            &PayoutRecipient {
+                wallet: ctx.accounts.config.penalty_wallet,
+                wallet_info: Some(&ctx.accounts.penalty_wallet),
+                token_account: ctx.accounts.penalty_wallet_token_account.as_deref(),
+            },
+            referrer_recipient.as_ref(),
+            &shares,
+        )?;
+
+        record_slash(&mut ctx.accounts.reputation, &ctx.accounts.escrow_account, penalty_amount)?;
+
+        emit!(SlashFinalized {
+            tenant_profile_id: slash_dispute.tenant_profile_id.clone(),
+            apartment_id: slash_dispute.apartment_id.clone(),
+            staker: slash_dispute.staker,
+            amount: penalty_amount,
+            lessor_amount: shares.lessor,
+            penalty_wallet_amount: shares.penalty_wallet,
+            referrer_amount: shares.referrer,
+            referrer: referrer_pubkey,
+        });
+
+        Ok(())
+    }
+
+    /// Refund unruled dispute (permissionless - the arbiter panel missed the ruling deadline)
+    /// Returns the held funds to the staker in full, the slash never stood
+    #[allow(unused_variables)]
+    pub fn refund_unruled_dispute(
+        ctx: Context<RefundUnruledDispute>,
+        apartment_hash: [u8; 32],
+        profile_hash: [u8; 32],
+    ) -> Result<()> {
+        let slash_dispute = &ctx.accounts.slash_dispute;
+
+        require!(slash_dispute.status == DisputeStatus::Disputed, EscrowError::InvalidDisputeStatus);
+
+        let now = Clock::get()?.unix_timestamp;
+        require!(now > slash_dispute.ruling_deadline, EscrowError::RulingWindowOpen);
+
+        let refund_amount = slash_dispute.amount;
+
+        let escrow_account = &mut ctx.accounts.escrow_account;
+        escrow_account.pending_slashes = escrow_account.pending_slashes
+            .checked_sub(1)
             .ok_or(EscrowError::ArithmeticOverflow)?;
 
-        msg!("Referrer reward: {}", referrer_reward);
-        msg!("Staker amount: {}", staker_amount);
+        let stake_vault = load_stake_vault(
+            &ctx.accounts.escrow_account,
+            ctx.accounts.stake_mint.as_deref(),
+            ctx.accounts.stake_vault.as_deref(),
+            ctx.accounts.token_program.as_ref(),
+        )?;
+        let staker_recipient = PayoutRecipient {
+            wallet: slash_dispute.staker,
+            wallet_info: Some(&ctx.accounts.staker),
+            token_account: ctx.accounts.staker_token_account.as_deref(),
+        };
+        pay_from_escrow(
+            &ctx.accounts.escrow_account,
+            &apartment_hash,
+            stake_vault.as_ref(),
+            &staker_recipient,
+            refund_amount,
+        )?;
+
+        msg!("Ruling deadline passed, refunded {} to staker: {}", refund_amount, slash_dispute.staker);
+
+        emit!(UnruledDisputeRefunded {
+            tenant_profile_id: slash_dispute.tenant_profile_id.clone(),
+            apartment_id: slash_dispute.apartment_id.clone(),
+            staker: slash_dispute.staker,
+            amount: refund_amount,
+            ruling_deadline: slash_dispute.ruling_deadline,
+        });
+
+        Ok(())
+    }
+
+    /// Resolve stake (lessor action - tenant fulfilled terms)
+    /// Referrer reward is fixed by the escrow terms set at initialization, once the referrer reached the referral limit
+    /// the staker keeps it
+    /// Only the approved tenant's stake pays the protocol fee and referral reward, other applicants get a full refund
+    /// line_items, when given, must itemize exactly what is withheld from the staker (fee and referral reward)
+    pub fn resolve_stake(
+        ctx: Context<ResolveStake>,
+        apartment_hash: [u8; 32],
+        profile_hash: [u8; 32],
+        apartment_id: String,
+        tenant_profile_id: String,
+        apartment_owner: Pubkey,
+        line_items: Vec<DeductionItem>,
+    ) -> Result<()> {
+        require_payouts_allowed(&ctx.accounts.config)?;
+
+        let reward_amount = ctx.accounts.escrow_account.referral_reward;
+        let approved_tenant = ctx.accounts.escrow_account.approved_profile == Some(profile_hash);
+
+        // Only the referrer the staker consented to on-chain can be paid, and only for the approved tenant
+        let referrer_pubkey = if approved_tenant {
+            load_referral(&ctx.accounts.referral, &ctx.accounts.stake_record.staker)?
+                .map(|referral| referral.referrer)
+        } else {
+            None
+        };
+
+        // Referrers are never paid past the apartment's cap, the staker keeps the reward instead
+        let referrer_pubkey = match referrer_pubkey {
+            Some(referrer_pubkey) if reward_amount > 0 => {
+                let referrer_counter = ctx.accounts.referrer_counter.as_ref()
+                    .ok_or(EscrowError::MissingReferrerCounter)?;
+                require!(referrer_counter.referrer == referrer_pubkey, EscrowError::InvalidReferrerCounter);
+                require!(referrer_counter.apartment_id == apartment_id, EscrowError::InvalidReferrerCounter);
+                if referrer_counter.referrals_paid < ctx.accounts.escrow_account.referral_limit {
+                    Some(referrer_pubkey)
+                } else {
+                    msg!("Referrer {} reached the referral limit, reward stays with the staker", referrer_pubkey);
+                    None
+                }
+            }
+            _ => None,
+        };
+
+        let escrow_account = &mut ctx.accounts.escrow_account;
+        let stake_record = &mut ctx.accounts.stake_record;
+
+        require_escrow_status(escrow_account, &[EscrowStatus::TenantApproved, EscrowStatus::LeaseActive])?;
+        require!(stake_record.status == StakeStatus::Active, EscrowError::StakeNotActive);
+        require!(ctx.accounts.lessor.key() == apartment_owner, EscrowError::UnauthorizedLessor);
+        require!(escrow_account.lessor == apartment_owner, EscrowError::UnauthorizedLessor);
+        require!(stake_record.apartment_id == apartment_id, EscrowError::InvalidApartment);
+        require!(stake_record.tenant_profile_id == tenant_profile_id, EscrowError::InvalidTenant);
+
+        let stake_record_amount = stake_record.amount;
+        let escrow_total_staked = escrow_account.total_staked;
+        let staker = stake_record.staker;
+        
+        // Use the minimum of what the stake record claims and what's available in escrow
+        let total_transfer_amount = std::cmp::min(stake_record_amount, escrow_total_staked);
+        require!(total_transfer_amount > 0, EscrowError::InsufficientFunds);
+
+        // Protocol fee comes off the top of the approved tenant's resolved amount
+        let protocol_fee = if approved_tenant {
+            apply_bps(total_transfer_amount, ctx.accounts.config.resolution_fee_bps)?
+        } else {
+            0
+        };
+        let after_fee_amount = total_transfer_amount
+            .checked_sub(protocol_fee)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+
+        // Calculate referrer reward and remaining amount for staker
+        let referrer_reward = if referrer_pubkey.is_some() && reward_amount > 0 {
+            std::cmp::min(reward_amount, after_fee_amount)
+        } else {
+            0
+        };
+        
+        let staker_amount = after_fee_amount
+            .checked_sub(referrer_reward)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+
+        let withheld_amount = total_transfer_amount
+            .checked_sub(staker_amount)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+        let itemized_amount = total_deductions(&line_items)?;
+        require!(
+            line_items.is_empty() || itemized_amount == withheld_amount,
+            EscrowError::LineItemsMismatch
+        );
+
+        msg!(
+            "Resolving {} for {} - protocol fee: {}, referrer reward: {}, staker: {}",
+            total_transfer_amount,
+            tenant_profile_id,
+            protocol_fee,
+            referrer_reward,
+            staker_amount
+        );
+
+        stake_record.status = StakeStatus::Settled;
+        escrow_account.active_stakes = escrow_account.active_stakes
+            .checked_sub(1)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+        
+        // Update stake record to reflect what was actually transferred
+        stake_record.amount = stake_record_amount
+            .checked_sub(total_transfer_amount)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+
+        // Update total staked in escrow
+        escrow_account.total_staked = escrow_total_staked
+            .checked_sub(total_transfer_amount)
+            .ok_or(EscrowError::InsufficientFunds)?;
+
+        let stake_vault = load_stake_vault(
+            &ctx.accounts.escrow_account,
+            ctx.accounts.stake_mint.as_deref(),
+            ctx.accounts.stake_vault.as_deref(),
+            ctx.accounts.token_program.as_ref(),
+        )?;
+
+        // Transfer protocol fee to the treasury
+        if protocol_fee > 0 {
+            let treasury_info = ctx.accounts.treasury.to_account_info();
+            let treasury_recipient = PayoutRecipient {
+                wallet: treasury_info.key(),
+                wallet_info: Some(&treasury_info),
+                token_account: ctx.accounts.treasury_token_account.as_deref(),
+            };
+            pay_from_escrow(
+                &ctx.accounts.escrow_account,
+                &apartment_hash,
+                stake_vault.as_ref(),
+                &treasury_recipient,
+                protocol_fee,
+            )?;
+
+            msg!("Transferred {} to treasury", protocol_fee);
+        }
+
+        // Transfer referrer reward if applicable
+        if let (true, Some(referrer_pubkey)) = (referrer_reward > 0, referrer_pubkey) {
+            let referrer_recipient = PayoutRecipient {
+                wallet: referrer_pubkey,
+                wallet_info: ctx.accounts.referrer.as_ref(),
+                token_account: ctx.accounts.referrer_token_account.as_deref(),
+            };
+
+            // Count the payout against the per-referrer cap for this apartment
+            let referrer_counter = ctx.accounts.referrer_counter.as_mut()
+                .ok_or(EscrowError::MissingReferrerCounter)?;
+            referrer_counter.referrals_paid = referrer_counter.referrals_paid
+                .checked_add(1)
+                .ok_or(EscrowError::ArithmeticOverflow)?;
+            
+            pay_from_escrow(
+                &ctx.accounts.escrow_account,
+                &apartment_hash,
+                stake_vault.as_ref(),
+                &referrer_recipient,
+                referrer_reward,
+            )?;
+            
+            msg!("Transferred {} to referrer: {}", referrer_reward, referrer_pubkey);
+        }
+
+        // Transfer remaining amount to original staker
+        if staker_amount > 0 {
+            let staker_recipient = PayoutRecipient {
+                wallet: staker,
+                wallet_info: Some(&ctx.accounts.staker),
+                token_account: ctx.accounts.staker_token_account.as_deref(),
+            };
+            pay_from_escrow(
+                &ctx.accounts.escrow_account,
+                &apartment_hash,
+                stake_vault.as_ref(),
+                &staker_recipient,
+                staker_amount,
+            )?;
+            
+            msg!("Transferred {} to staker: {}", staker_amount, staker);
+        }
+
+        // Only the approved tenant's stake, resolved once the lease ran, counts as completed
+        // Other applicants omit their reputation, so the lessor never pays its rent for them
+        let escrow_account = &ctx.accounts.escrow_account;
+        let completed_lease = approved_tenant && escrow_account.status == EscrowStatus::LeaseActive;
+        if let Some(reputation) = ctx.accounts.reputation.as_mut() {
+            reputation.profile_hash = profile_hash;
+            reputation.owner = staker;
+            reputation.bump = ctx.bumps.reputation.ok_or(EscrowError::MissingReputation)?;
+            if completed_lease {
+                record_completed_stake(reputation, escrow_account, total_transfer_amount)?;
+            }
+        } else {
+            require!(!completed_lease, EscrowError::MissingReputation);
+        }
+
+        if approved_tenant {
+            end_listing(&mut ctx.accounts.escrow_account);
+        }
+
+        emit!(StakeResolved {
+            tenant_profile_id,
+            apartment_id,
+            staker,
+            amount: total_transfer_amount,
+            referrer_reward,
+            referrer: referrer_pubkey,
+            protocol_fee,
+            line_items,
+        });
+
+        Ok(())
+    }
+
+    /// Mutual release (lessor and staker action - settle a stake on terms both sides agreed)
+    /// lessor_amount and referrer_amount come out of the stake, the staker gets the rest
+    /// Both parties sign, so no dispute window or deadline applies
+    /// Deposits with a pending move-out settlement go through release_deposit instead
+    #[allow(unused_variables)]
+    pub fn mutual_release(
+        ctx: Context<MutualRelease>,
+        apartment_hash: [u8; 32],
+        profile_hash: [u8; 32],
+        lessor_amount: u64,
+        referrer_amount: u64,
+    ) -> Result<()> {
+        require_payouts_allowed(&ctx.accounts.config)?;
+
+        // Only the referrer the staker consented to on-chain can be paid
+        let referrer_pubkey = load_referral(&ctx.accounts.referral, &ctx.accounts.stake_record.staker)?
+            .map(|referral| referral.referrer);
+        require!(referrer_amount == 0 || referrer_pubkey.is_some(), EscrowError::InvalidReferral);
+
+        let escrow_account = &mut ctx.accounts.escrow_account;
+        let stake_record = &mut ctx.accounts.stake_record;
+
+        require!(
+            matches!(stake_record.status, StakeStatus::Active | StakeStatus::Deposit),
+            EscrowError::StakeNotActive
+        );
+        require!(ctx.accounts.move_out_settlement.data_is_empty(), EscrowError::MoveOutPending);
+        // Deposits only exist while the lease runs, application stakes can be released in any phase
+        if stake_record.status == StakeStatus::Deposit {
+            require_escrow_status(escrow_account, &[EscrowStatus::LeaseActive, EscrowStatus::MoveOutReview])?;
+        } else {
+            require_escrow_status(
+                escrow_account,
+                &[
+                    EscrowStatus::Open,
+                    EscrowStatus::TenantApproved,
+                    EscrowStatus::LeaseActive,
+                    EscrowStatus::MoveOutReview,
+                    EscrowStatus::Closed,
+                ],
+            )?;
+        }
+
+        let release_amount = stake_record.amount;
+        let staker_amount = release_amount
+            .checked_sub(lessor_amount)
+            .and_then(|rest| rest.checked_sub(referrer_amount))
+            .ok_or(EscrowError::InvalidReleaseSplit)?;
+
+        stake_record.status = StakeStatus::Settled;
+        stake_record.amount = 0;
+        escrow_account.active_stakes = escrow_account.active_stakes
+            .checked_sub(1)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+        if escrow_account.approved_profile == Some(profile_hash) {
+            end_listing(escrow_account);
+        }
+        escrow_account.total_staked = escrow_account.total_staked
+            .checked_sub(release_amount)
+            .ok_or(EscrowError::InsufficientFunds)?;
+
+        let stake_vault = load_stake_vault(
+            &ctx.accounts.escrow_account,
+            ctx.accounts.stake_mint.as_deref(),
+            ctx.accounts.stake_vault.as_deref(),
+            ctx.accounts.token_program.as_ref(),
+        )?;
+        let lessor_recipient = PayoutRecipient {
+            wallet: ctx.accounts.lessor.key(),
+            wallet_info: Some(&ctx.accounts.lessor),
+            token_account: ctx.accounts.lessor_token_account.as_deref(),
+        };
+        pay_from_escrow(
+            &ctx.accounts.escrow_account,
+            &apartment_hash,
+            stake_vault.as_ref(),
+            &lessor_recipient,
+            lessor_amount,
+        )?;
+        if let (true, Some(referrer_pubkey)) = (referrer_amount > 0, referrer_pubkey) {
+            let referrer_recipient = PayoutRecipient {
+                wallet: referrer_pubkey,
+                wallet_info: ctx.accounts.referrer.as_ref(),
+                token_account: ctx.accounts.referrer_token_account.as_deref(),
+            };
+
+            // A paid referral counts against the per-referrer cap like a resolved one
+            let referrer_counter = ctx.accounts.referrer_counter.as_mut()
+                .ok_or(EscrowError::MissingReferrerCounter)?;
+            require!(referrer_counter.referrer == referrer_pubkey, EscrowError::InvalidReferrerCounter);
+            require!(
+                referrer_counter.referrals_paid < ctx.accounts.escrow_account.referral_limit,
+                EscrowError::ReferralLimitReached
+            );
+            referrer_counter.referrals_paid = referrer_counter.referrals_paid
+                .checked_add(1)
+                .ok_or(EscrowError::ArithmeticOverflow)?;
+
+            pay_from_escrow(
+                &ctx.accounts.escrow_account,
+                &apartment_hash,
+                stake_vault.as_ref(),
+                &referrer_recipient,
+                referrer_amount,
+            )?;
+        }
+        let staker_recipient = PayoutRecipient {
+            wallet: ctx.accounts.staker.key(),
+            wallet_info: Some(&ctx.accounts.staker),
+            token_account: ctx.accounts.staker_token_account.as_deref(),
+        };
+        pay_from_escrow(
+            &ctx.accounts.escrow_account,
+            &apartment_hash,
+            stake_vault.as_ref(),
+            &staker_recipient,
+            staker_amount,
+        )?;
+
+        msg!(
+            "Mutually released {} - lessor: {}, referrer: {}, staker: {}",
+            release_amount,
+            lessor_amount,
+            referrer_amount,
+            staker_amount
+        );
+
+        let stake_record = &ctx.accounts.stake_record;
+        emit!(MutualReleased {
+            tenant_profile_id: stake_record.tenant_profile_id.clone(),
+            apartment_id: stake_record.apartment_id.clone(),
+            staker: stake_record.staker,
+            lessor: ctx.accounts.lessor.key(),
+            lessor_amount,
+            referrer_amount,
+            staker_amount,
+            referrer: referrer_pubkey,
+        });
+
+        Ok(())
+    }
+
+    /// Register referral (referrer action with tenant consent - before the tenant stakes)
+    /// Records who gets the referral reward when this profile's stake resolves
+    /// The tenant signs the consent message with their wallet off-chain, the referrer submits it in an
+    /// ed25519 instruction right before this one, and only a stake from that same wallet pays the referral
+    #[allow(unused_variables)]
+    pub fn register_referral(
+        ctx: Context<RegisterReferral>,
+        apartment_hash: [u8; 32],
+        profile_hash: [u8; 32],
+        apartment_id: String,
+        tenant_profile_id: String,
+    ) -> Result<()> {
+        require!(!apartment_id.is_empty(), EscrowError::InvalidApartment);
+        require!(!tenant_profile_id.is_empty(), EscrowError::InvalidTenant);
+        require!(profile_hash == hash_string(&tenant_profile_id), EscrowError::InvalidTenant);
+
+        let escrow_account = &ctx.accounts.escrow_account;
+        require_escrow_status(escrow_account, &[EscrowStatus::Open])?;
+        require!(escrow_account.apartment_id == apartment_id, EscrowError::InvalidApartment);
+
+        // Referral must exist before the tenant's stake record is created
+        require!(ctx.accounts.stake_record.data_is_empty(), EscrowError::ReferralAfterStake);
+
+        // Tenant consents off-chain, so the referrer can submit whenever they like
+        let consent_message = referral_consent_message(
+            &ctx.accounts.referrer.key(),
+            &tenant_profile_id,
+            &apartment_id,
+        );
+        require_ed25519_signature(&ctx.accounts.instructions, &ctx.accounts.tenant.key(), &consent_message)?;
+
+        // Refuse referrals that could never be paid out under this escrow's cap
+        let referral_limit = escrow_account.referral_limit;
+        let referrer_counter = &mut ctx.accounts.referrer_counter;
+        require!(referrer_counter.referrals_paid < referral_limit, EscrowError::ReferralLimitReached);
+
+        referrer_counter.referrer = ctx.accounts.referrer.key();
+        referrer_counter.apartment_id = apartment_id.clone();
+        referrer_counter.bump = ctx.bumps.referrer_counter;
+
+        let referral = &mut ctx.accounts.referral;
+        referral.referrer = ctx.accounts.referrer.key();
+        referral.apartment_id = apartment_id.clone();
+        referral.tenant_profile_id = tenant_profile_id.clone();
+        referral.bump = ctx.bumps.referral;
+        referral.tenant = ctx.accounts.tenant.key();
+
+        emit!(ReferralRegistered {
+            tenant_profile_id,
+            apartment_id,
+            referrer: ctx.accounts.referrer.key(),
+            tenant: ctx.accounts.tenant.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Refund applicants (lessor action - listing filled)
+    /// remaining_accounts: [stake_record, staker] pairs, one per entry of profile_hashes, approved profile is skipped
+    /// Token escrows pass the staker's token account in place of the staker
+    pub fn refund_applicants<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RefundApplicants<'info>>,
+        apartment_hash: [u8; 32],
+        apartment_id: String,
+        profile_hashes: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        require_payouts_allowed(&ctx.accounts.config)?;
+
+        let escrow_account = &ctx.accounts.escrow_account;
+
+        require!(escrow_account.apartment_id == apartment_id, EscrowError::InvalidApartment);
+        require!(ctx.accounts.lessor.key() == escrow_account.lessor, EscrowError::UnauthorizedLessor);
+        // Applicants are only refunded once the listing is filled
+        require_escrow_status(
+            escrow_account,
+            &[
+                EscrowStatus::TenantApproved,
+                EscrowStatus::LeaseActive,
+                EscrowStatus::MoveOutReview,
+                EscrowStatus::Closed,
+            ],
+        )?;
+
+        let remaining_accounts = ctx.remaining_accounts;
+        let applicant_pairs = remaining_accounts.chunks_exact(2);
+        require!(
+            !remaining_accounts.is_empty()
+                && applicant_pairs.remainder().is_empty()
+                && applicant_pairs.len() == profile_hashes.len(),
+            EscrowError::InvalidRemainingAccounts
+        );
+
+        let stake_vault = load_stake_vault(
+            escrow_account,
+            ctx.accounts.stake_mint.as_deref(),
+            ctx.accounts.stake_vault.as_deref(),
+            ctx.accounts.token_program.as_ref(),
+        )?;
+
+        let mut total_refunded: u64 = 0;
+        let mut refunded_count: u32 = 0;
+
+        for (pair, profile_hash) in applicant_pairs.zip(profile_hashes.iter()) {
+            let stake_record_info = &pair[0];
+            let staker_info = &pair[1];
+
+            // Stake record of the approved tenant, if any, is never refunded here
+            if escrow_account.approved_profile == Some(*profile_hash) {
+                msg!("Skipping approved tenant stake record: {}", stake_record_info.key());
+                continue;
+            }
+
+            // Records that predate the current layout need migrate_account first, the rest of the batch still goes through
+            if stake_record_info.data_len() < 8 + StakeRecord::INIT_SPACE {
+                msg!("Skipping unmigrated stake record: {}", stake_record_info.key());
+                continue;
+            }
+
+            let mut stake_record = Account::<StakeRecord>::try_from(stake_record_info)?;
+            require!(stake_record.version == STAKE_RECORD_LAYOUT_VERSION, EscrowError::StakeRecordNotMigrated);
+            let expected_stake_record = Pubkey::create_program_address(
+                &[b"stake", apartment_hash.as_ref(), profile_hash.as_ref(), &[stake_record.bump]],
+                &crate::ID,
+            )
+            .map_err(|_| EscrowError::InvalidStakeRecord)?;
+            require!(stake_record_info.key() == expected_stake_record, EscrowError::InvalidStakeRecord);
+            require!(stake_record.apartment_id == apartment_id, EscrowError::InvalidApartment);
+
+            if stake_record.status != StakeStatus::Active {
+                msg!("Skipping inactive stake record: {}", stake_record_info.key());
+                continue;
+            }
+
+            let staker_token_account = match stake_vault {
+                Some(_) => Some(InterfaceAccount::<TokenAccount>::try_from(staker_info)?),
+                None => {
+                    require!(staker_info.key() == stake_record.staker, EscrowError::UnauthorizedStaker);
+                    None
+                }
+            };
+
+            let refund_amount = stake_record.amount;
+            stake_record.status = StakeStatus::Settled;
+            stake_record.amount = 0;
+            stake_record.exit(&crate::ID)?;
+
+            // Return the stake from the escrow to the original staker
+            let staker_recipient = PayoutRecipient {
+                wallet: stake_record.staker,
+                wallet_info: Some(staker_info),
+                token_account: staker_token_account.as_ref(),
+            };
+            pay_from_escrow(
+                escrow_account,
+                &apartment_hash,
+                stake_vault.as_ref(),
+                &staker_recipient,
+                refund_amount,
+            )?;
+
+            msg!("Refunded {} to staker: {}", refund_amount, stake_record.staker);
+
+            total_refunded = total_refunded
+                .checked_add(refund_amount)
+                .ok_or(EscrowError::ArithmeticOverflow)?;
+            refunded_count = refunded_count
+                .checked_add(1)
+                .ok_or(EscrowError::ArithmeticOverflow)?;
+        }
+
+        // Update escrow totals once for the whole batch
+        let escrow_account = &mut ctx.accounts.escrow_account;
+        escrow_account.total_staked = escrow_account.total_staked
+            .checked_sub(total_refunded)
+            .ok_or(EscrowError::InsufficientFunds)?;
+        escrow_account.active_stakes = escrow_account.active_stakes
+            .checked_sub(refunded_count)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+
+        msg!("Refunded {} applicants, {} total", refunded_count, total_refunded);
+
+        emit!(ApplicantsRefunded {
+            apartment_id,
+            refunded_count,
+            total_refunded,
+        });
+
+        Ok(())
+    }
+
+    /// Withdraw stake (staker action - tenant no longer interested)
+    /// Allowed for any active stake except the approved tenant's
+    pub fn withdraw_stake(
+        ctx: Context<WithdrawStake>,
+        apartment_hash: [u8; 32],
+        profile_hash: [u8; 32],
+        apartment_id: String,
+        tenant_profile_id: String,
+    ) -> Result<()> {
+        let escrow_account = &mut ctx.accounts.escrow_account;
+        let stake_record = &mut ctx.accounts.stake_record;
+
+        require!(stake_record.status == StakeStatus::Active, EscrowError::StakeNotActive);
+        require!(ctx.accounts.staker.key() == stake_record.staker, EscrowError::UnauthorizedStaker);
+        require!(stake_record.apartment_id == apartment_id, EscrowError::InvalidApartment);
+        require!(stake_record.tenant_profile_id == tenant_profile_id, EscrowError::InvalidTenant);
+        require!(escrow_account.approved_profile != Some(profile_hash), EscrowError::ApprovedTenantCannotWithdraw);
+        // Applicants who were not approved can take their stake back in every phase
+        require_escrow_status(
+            escrow_account,
+            &[
+                EscrowStatus::Open,
+                EscrowStatus::TenantApproved,
+                EscrowStatus::LeaseActive,
+                EscrowStatus::MoveOutReview,
+                EscrowStatus::Closed,
+            ],
+        )?;
+
+        let withdraw_amount = stake_record.amount;
+        require!(withdraw_amount > 0, EscrowError::InsufficientFunds);
+
+        stake_record.status = StakeStatus::Settled;
+        escrow_account.active_stakes = escrow_account.active_stakes
+            .checked_sub(1)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+        stake_record.amount = 0;
+
+        // Update total staked in escrow
+        escrow_account.total_staked = escrow_account.total_staked
+            .checked_sub(withdraw_amount)
+            .ok_or(EscrowError::InsufficientFunds)?;
+
+        // Return the stake from the escrow to the original staker
+        let stake_vault = load_stake_vault(
+            &ctx.accounts.escrow_account,
+            ctx.accounts.stake_mint.as_deref(),
+            ctx.accounts.stake_vault.as_deref(),
+            ctx.accounts.token_program.as_ref(),
+        )?;
+        let staker_recipient = PayoutRecipient {
+            wallet: ctx.accounts.staker.key(),
+            wallet_info: Some(&ctx.accounts.staker),
+            token_account: ctx.accounts.staker_token_account.as_deref(),
+        };
+        pay_from_escrow(
+            &ctx.accounts.escrow_account,
+            &apartment_hash,
+            stake_vault.as_ref(),
+            &staker_recipient,
+            withdraw_amount,
+        )?;
+
+        msg!("Withdrew {} to staker: {}", withdraw_amount, ctx.accounts.staker.key());
+
+        emit!(StakeWithdrawn {
+            tenant_profile_id,
+            apartment_id,
+            staker: ctx.accounts.staker.key(),
+            amount: withdraw_amount,
+        });
+
+        Ok(())
+    }
+
+    /// Refund expired stake (permissionless - lessor missed the decision deadline)
+    /// Returns the full stake to the original staker
+    pub fn refund_expired_stake(
+        ctx: Context<RefundExpiredStake>,
+        apartment_hash: [u8; 32],
+        profile_hash: [u8; 32],
+        apartment_id: String,
+        tenant_profile_id: String,
+    ) -> Result<()> {
+        let escrow_account = &mut ctx.accounts.escrow_account;
+        let stake_record = &mut ctx.accounts.stake_record;
+
+        require!(stake_record.status == StakeStatus::Active, EscrowError::StakeNotActive);
+        require!(stake_record.apartment_id == apartment_id, EscrowError::InvalidApartment);
+        require!(stake_record.tenant_profile_id == tenant_profile_id, EscrowError::InvalidTenant);
+
+        // Once the lease starts, the approved tenant's stake no longer expires with the decision deadline
+        if escrow_account.approved_profile == Some(profile_hash) {
+            require_escrow_status(escrow_account, &[EscrowStatus::TenantApproved])?;
+        }
+
+        let decision_deadline = escrow_account.decision_deadline;
+        let now = Clock::get()?.unix_timestamp;
+        require!(now > decision_deadline, EscrowError::DecisionWindowOpen);
+
+        let refund_amount = stake_record.amount;
+        require!(refund_amount > 0, EscrowError::InsufficientFunds);
+
+        stake_record.status = StakeStatus::Settled;
+        escrow_account.active_stakes = escrow_account.active_stakes
+            .checked_sub(1)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+        if escrow_account.approved_profile == Some(profile_hash) {
+            end_listing(escrow_account);
+        }
+        stake_record.amount = 0;
+
+        // Update total staked in escrow
+        escrow_account.total_staked = escrow_account.total_staked
+            .checked_sub(refund_amount)
+            .ok_or(EscrowError::InsufficientFunds)?;
+
+        // Return the stake from the escrow to the original staker
+        let stake_vault = load_stake_vault(
+            &ctx.accounts.escrow_account,
+            ctx.accounts.stake_mint.as_deref(),
+            ctx.accounts.stake_vault.as_deref(),
+            ctx.accounts.token_program.as_ref(),
+        )?;
+        let staker_recipient = PayoutRecipient {
+            wallet: ctx.accounts.staker.key(),
+            wallet_info: Some(&ctx.accounts.staker),
+            token_account: ctx.accounts.staker_token_account.as_deref(),
+        };
+        pay_from_escrow(
+            &ctx.accounts.escrow_account,
+            &apartment_hash,
+            stake_vault.as_ref(),
+            &staker_recipient,
+            refund_amount,
+        )?;
+
+        msg!("Deadline passed, refunded {} to staker: {}", refund_amount, ctx.accounts.staker.key());
+
+        emit!(ExpiredStakeRefunded {
+            tenant_profile_id,
+            apartment_id,
+            staker: ctx.accounts.staker.key(),
+            amount: refund_amount,
+            decision_deadline,
+        });
+
+        Ok(())
+    }
+
+    /// Close stake record (staker action - after the stake was settled)
+    /// Closes the PDA and returns its rent to the staker
+    #[allow(unused_variables)]
+    pub fn close_stake_record(
+        ctx: Context<CloseStakeRecord>,
+        apartment_hash: [u8; 32],
+        profile_hash: [u8; 32],
+        apartment_id: String,
+        tenant_profile_id: String,
+    ) -> Result<()> {
+        let stake_record = &ctx.accounts.stake_record;
+
+        require!(stake_record.status == StakeStatus::Settled, EscrowError::StakeStillActive);
+        require!(stake_record.apartment_id == apartment_id, EscrowError::InvalidApartment);
+        require!(stake_record.tenant_profile_id == tenant_profile_id, EscrowError::InvalidTenant);
+
+        // The approved tenant's record is the lease's evidence until the listing is closed
+        let escrow_info = &ctx.accounts.escrow_account;
+        if !escrow_info.data_is_empty() {
+            let escrow_account = ApartmentEscrow::try_deserialize(&mut &escrow_info.try_borrow_data()?[..])?;
+            if escrow_account.approved_profile == Some(profile_hash) {
+                require_escrow_status(&escrow_account, &[EscrowStatus::Closed])?;
+            }
+        }
+
+        msg!("Closing stake record, returning rent to: {}", ctx.accounts.staker.key());
+
+        emit!(StakeRecordClosed {
+            tenant_profile_id,
+            apartment_id,
+            staker: ctx.accounts.staker.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Propose lessor transfer (lessor action - property sold or handed over)
+    /// The new lessor must accept before ownership changes
+    #[allow(unused_variables)]
+    pub fn propose_lessor_transfer(
+        ctx: Context<ProposeLessorTransfer>,
+        apartment_hash: [u8; 32],
+        apartment_id: String,
+        new_lessor: Pubkey,
+    ) -> Result<()> {
+        let escrow_account = &mut ctx.accounts.escrow_account;
+
+        require!(escrow_account.apartment_id == apartment_id, EscrowError::InvalidApartment);
+        require!(ctx.accounts.lessor.key() == escrow_account.lessor, EscrowError::UnauthorizedLessor);
+        require!(new_lessor != escrow_account.lessor, EscrowError::InvalidNewLessor);
+
+        escrow_account.pending_lessor = Some(new_lessor);
+
+        emit!(LessorTransferProposed {
+            apartment_id,
+            current_lessor: escrow_account.lessor,
+            proposed_lessor: new_lessor,
+        });
+
+        Ok(())
+    }
+
+    /// Cancel lessor transfer (lessor action - withdraw a proposal before it is accepted)
+    #[allow(unused_variables)]
+    pub fn cancel_lessor_transfer(
+        ctx: Context<CancelLessorTransfer>,
+        apartment_hash: [u8; 32],
+        apartment_id: String,
+    ) -> Result<()> {
+        let escrow_account = &mut ctx.accounts.escrow_account;
+
+        require!(escrow_account.apartment_id == apartment_id, EscrowError::InvalidApartment);
+        require!(ctx.accounts.lessor.key() == escrow_account.lessor, EscrowError::UnauthorizedLessor);
+
+        let cancelled_lessor = escrow_account.pending_lessor
+            .take()
+            .ok_or(EscrowError::NoPendingLessorTransfer)?;
+
+        emit!(LessorTransferCancelled {
+            apartment_id,
+            lessor: escrow_account.lessor,
+            cancelled_lessor,
+        });
+
+        Ok(())
+    }
+
+    /// Accept lessor transfer (proposed lessor action)
+    #[allow(unused_variables)]
+    pub fn accept_lessor_transfer(
+        ctx: Context<AcceptLessorTransfer>,
+        apartment_hash: [u8; 32],
+        apartment_id: String,
+    ) -> Result<()> {
+        let escrow_account = &mut ctx.accounts.escrow_account;
+
+        require!(escrow_account.apartment_id == apartment_id, EscrowError::InvalidApartment);
+
+        let pending_lessor = escrow_account.pending_lessor
+            .ok_or(EscrowError::NoPendingLessorTransfer)?;
+        require!(ctx.accounts.new_lessor.key() == pending_lessor, EscrowError::UnauthorizedPendingLessor);
+
+        let previous_lessor = escrow_account.lessor;
+        escrow_account.lessor = pending_lessor;
+        escrow_account.pending_lessor = None;
+
+        msg!("Lessor transferred from {} to {}", previous_lessor, pending_lessor);
+
+        emit!(LessorTransferAccepted {
+            apartment_id,
+            previous_lessor,
+            new_lessor: pending_lessor,
+        });
+
+        Ok(())
+    }
+
+    /// Migrate account (anyone - payer covers the extra rent)
+    /// Reallocs an ApartmentEscrow or StakeRecord written with an older layout to the current one
+    /// Legacy escrows must pass all their active stake records in remaining_accounts
+    /// Their lessor can sign to supply dispute terms the escrow never stored, without a signature
+    /// the decision deadline expires at migration and default dispute terms apply
+    pub fn migrate_account<'info>(
+        ctx: Context<'_, '_, 'info, 'info, MigrateAccount<'info>>,
+        legacy_escrow: Option<LegacyEscrowMigration>,
+    ) -> Result<()> {
+        let account_info = ctx.accounts.account.to_account_info();
+        require!(account_info.owner == &crate::ID, EscrowError::InvalidMigrationAccount);
+
+        let is_escrow = account_info.try_borrow_data()?.starts_with(ApartmentEscrow::DISCRIMINATOR);
+        let is_stake_record = account_info.try_borrow_data()?.starts_with(StakeRecord::DISCRIMINATOR);
+        require!(is_escrow || is_stake_record, EscrowError::InvalidMigrationAccount);
+
+        let current_len = account_info.data_len();
+        let target_len = if is_escrow {
+            8 + ApartmentEscrow::INIT_SPACE
+        } else {
+            8 + StakeRecord::INIT_SPACE
+        };
+
+        // Grow the account and charge the payer only the extra rent
+        if current_len < target_len {
+            let rent = Rent::get()?;
+            let rent_difference = rent
+                .minimum_balance(target_len)
+                .saturating_sub(rent.minimum_balance(current_len));
+
+            if rent_difference > 0 {
+                let cpi_context = CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    system_program::Transfer {
+                        from: ctx.accounts.payer.to_account_info(),
+                        to: account_info.clone(),
+                    },
+                );
+                system_program::transfer(cpi_context, rent_difference)?;
+            }
+
+            // New bytes are zeroed, so appended fields read as their defaults
+            account_info.resize(target_len)?;
+            msg!("Resized account from {} to {} bytes", current_len, target_len);
+        }
+
+        let (previous_version, new_version) = if is_escrow {
+            let mut escrow_account = ApartmentEscrow::try_deserialize(&mut &account_info.try_borrow_data()?[..])?;
+            require!(escrow_account.version < ESCROW_LAYOUT_VERSION, EscrowError::AccountAlreadyMigrated);
+            let previous_version = escrow_account.version;
+
+            let legacy_escrow = legacy_escrow.as_ref().ok_or(EscrowError::MissingLegacyEscrowTerms)?;
+            let apartment_hash = legacy_escrow.apartment_hash;
+
+            let expected_escrow = Pubkey::create_program_address(
+                &[b"escrow", apartment_hash.as_ref(), &[escrow_account.bump]],
+                &crate::ID,
+            )
+            .map_err(|_| EscrowError::InvalidMigrationAccount)?;
+            require!(account_info.key() == expected_escrow, EscrowError::InvalidMigrationAccount);
+
+            // Legacy escrows never tracked these, rebuild them from what is on-chain
+            if escrow_account.rent_payer == Pubkey::default() {
+                escrow_account.rent_payer = escrow_account.lessor;
+            }
+
+            // Dispute terms that read as zero come from the lessor when they sign, otherwise
+            // legacy defaults apply and the decision window is already over so stakers can refund
+            let lessor_signed = match ctx.accounts.lessor.as_ref() {
+                Some(lessor) => {
+                    require!(lessor.key() == escrow_account.lessor, EscrowError::UnauthorizedLessor);
+                    true
+                }
+                None => false,
+            };
+            let missing_slash_split = escrow_account.slash_split.lessor_bps == 0
+                && escrow_account.slash_split.penalty_wallet_bps == 0
+                && escrow_account.slash_split.referrer_bps == 0;
+
+            if escrow_account.decision_deadline == 0 {
+                let now = Clock::get()?.unix_timestamp;
+                escrow_account.decision_deadline = if lessor_signed {
+                    require!(legacy_escrow.decision_window > 0, EscrowError::InvalidDecisionWindow);
+                    now.checked_add(legacy_escrow.decision_window)
+                        .ok_or(EscrowError::ArithmeticOverflow)?
+                } else {
+                    now
+                };
+            }
+            if escrow_account.dispute_window == 0 {
+                escrow_account.dispute_window = if lessor_signed {
+                    require!(legacy_escrow.dispute_window > 0, EscrowError::InvalidDisputeWindow);
+                    legacy_escrow.dispute_window
+                } else {
+                    LEGACY_DISPUTE_WINDOW
+                };
+            }
+            if escrow_account.arbiter_panel == Pubkey::default() {
+                let arbiter_panel = ctx.accounts.arbiter_panel.as_ref()
+                    .ok_or(EscrowError::InvalidArbiterPanel)?;
+                escrow_account.arbiter_panel = arbiter_panel.key();
+            }
+            if missing_slash_split {
+                escrow_account.slash_split = if lessor_signed {
+                    require_valid_slash_split(&legacy_escrow.slash_split)?;
+                    legacy_escrow.slash_split
+                } else {
+                    // Legacy slashes paid everything to the penalty wallet
+                    SlashSplit {
+                        lessor_bps: 0,
+                        penalty_wallet_bps: BPS_DENOMINATOR as u16,
+                        referrer_bps: 0,
+                    }
+                };
+            }
+
+            let stake_records = ctx.remaining_accounts;
+            require!(
+                stake_records.len() == legacy_escrow.profile_hashes.len(),
+                EscrowError::InvalidRemainingAccounts
+            );
+
+            let mut seen_records: Vec<Pubkey> = Vec::new();
+            let mut active_stakes: u32 = 0;
+            let mut active_amount: u64 = 0;
+
+            for (stake_record_info, profile_hash) in stake_records.iter().zip(legacy_escrow.profile_hashes.iter()) {
+                require!(stake_record_info.owner == &crate::ID, EscrowError::InvalidMigrationAccount);
+                require!(!seen_records.contains(stake_record_info.key), EscrowError::InvalidRemainingAccounts);
+                seen_records.push(stake_record_info.key());
+
+                let data = stake_record_info.try_borrow_data()?;
+                require!(data.starts_with(StakeRecord::DISCRIMINATOR), EscrowError::InvalidMigrationAccount);
+                let stake_record = StakeRecordPrefix::deserialize(&mut &data[8..])?;
+
+                let expected_stake_record = Pubkey::create_program_address(
+                    &[b"stake", apartment_hash.as_ref(), profile_hash.as_ref(), &[stake_record.bump]],
+                    &crate::ID,
+                )
+                .map_err(|_| EscrowError::InvalidStakeRecord)?;
+                require!(stake_record_info.key() == expected_stake_record, EscrowError::InvalidStakeRecord);
+                require!(stake_record.apartment_id == escrow_account.apartment_id, EscrowError::InvalidApartment);
+
+                if stake_record.status != StakeStatus::Settled {
+                    msg!(
+                        "Counting active stake of {} for {} ({})",
+                        stake_record.amount,
+                        stake_record.tenant_profile_id,
+                        stake_record.staker
+                    );
+                    active_stakes = active_stakes
+                        .checked_add(1)
+                        .ok_or(EscrowError::ArithmeticOverflow)?;
+                    active_amount = active_amount
+                        .checked_add(stake_record.amount)
+                        .ok_or(EscrowError::ArithmeticOverflow)?;
+                }
+            }
+
+            // Every active stake must be accounted for before the counter is trusted
+            require!(active_amount == escrow_account.total_staked, EscrowError::IncompleteStakeRecords);
+            escrow_account.active_stakes = active_stakes;
+
+            escrow_account.version = ESCROW_LAYOUT_VERSION;
+            escrow_account.try_serialize(&mut &mut account_info.try_borrow_mut_data()?[..])?;
+            (previous_version, ESCROW_LAYOUT_VERSION)
+        } else {
+            let mut stake_record = StakeRecord::try_deserialize(&mut &account_info.try_borrow_data()?[..])?;
+            require!(stake_record.version < STAKE_RECORD_LAYOUT_VERSION, EscrowError::AccountAlreadyMigrated);
+            let previous_version = stake_record.version;
+
+            stake_record.version = STAKE_RECORD_LAYOUT_VERSION;
+            stake_record.try_serialize(&mut &mut account_info.try_borrow_mut_data()?[..])?;
+            (previous_version, STAKE_RECORD_LAYOUT_VERSION)
+        };
+
+        msg!("Migrated account {} from v{} to v{}", account_info.key(), previous_version, new_version);
+
+        emit!(AccountMigrated {
+            account: account_info.key(),
+            previous_version,
+            new_version,
+            payer: ctx.accounts.payer.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Close escrow (lessor action - when rental period ends)
+    /// Closes the PDA and returns its rent to whoever paid for initialization
+    /// Legacy escrows deactivated before the status machine read as Closed and close the same way
+    pub fn close_escrow(ctx: Context<CloseEscrow>, apartment_hash: [u8; 32], apartment_id: String) -> Result<()> {
+        let escrow_account = &ctx.accounts.escrow_account;
+
+        require!(escrow_account.apartment_id == apartment_id, EscrowError::InvalidApartment);
+        require!(ctx.accounts.lessor.key() == escrow_account.lessor, EscrowError::UnauthorizedLessor);
+        // A filled listing closes itself once the tenant's stake or deposit is settled
+        require_escrow_status(escrow_account, &[EscrowStatus::Open, EscrowStatus::Closed])?;
+        require!(escrow_account.active_stakes == 0, EscrowError::EscrowNotEmpty);
+        require!(escrow_account.pending_slashes == 0, EscrowError::EscrowNotEmpty);
+        require!(escrow_account.total_staked == 0, EscrowError::EscrowNotEmpty);
+
+        // Token escrows close their vault too, any stray tokens sent to it go to the lessor first
+        let stake_vault = load_stake_vault(
+            escrow_account,
+            ctx.accounts.stake_mint.as_deref(),
+            ctx.accounts.stake_vault.as_deref(),
+            ctx.accounts.token_program.as_ref(),
+        )?;
+        if let Some(stake_vault) = stake_vault {
+            let lessor_recipient = PayoutRecipient {
+                wallet: escrow_account.lessor,
+                wallet_info: None,
+                token_account: ctx.accounts.lessor_token_account.as_deref(),
+            };
+            pay_from_escrow(
+                escrow_account,
+                &apartment_hash,
+                Some(&stake_vault),
+                &lessor_recipient,
+                stake_vault.vault.amount,
+            )?;
+
+            let bump = [escrow_account.bump];
+            let escrow_seeds: [&[u8]; 3] = [b"escrow", apartment_hash.as_ref(), &bump];
+            let signer_seeds = [&escrow_seeds[..]];
+            let cpi_context = CpiContext::new_with_signer(
+                stake_vault.token_program.to_account_info(),
+                CloseAccount {
+                    account: stake_vault.vault.to_account_info(),
+                    destination: ctx.accounts.rent_payer.to_account_info(),
+                    authority: escrow_account.to_account_info(),
+                },
+                &signer_seeds,
+            );
+            token_interface::close_account(cpi_context)?;
+        }
+
+        // Legacy escrows deactivated through the old is_active flag already read as Closed
+        if ctx.accounts.escrow_account.status != EscrowStatus::Closed {
+            transition_escrow(&mut ctx.accounts.escrow_account, EscrowStatus::Closed);
+        }
+
+        msg!("Closing escrow, returning rent to: {}", ctx.accounts.rent_payer.key());
+
+        emit!(EscrowClosed {
+            apartment_id,
+            lessor: ctx.accounts.lessor.key(),
+            rent_payer: ctx.accounts.rent_payer.key(),
+        });
+
+        Ok(())
+    }
+}
+
+// ============================================================================
+// ACCOUNT CONTEXTS
+// ============================================================================
+
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    #[account(
+        init,
+        payer = admin,
+        seeds = [b"config"],
+        bump,
+        space = 8 + ProtocolConfig::INIT_SPACE
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        init,
+        payer = admin,
+        seeds = [b"treasury"],
+        bump,
+        space = 8 + Treasury::INIT_SPACE
+    )]
+    pub treasury: Account<'info, Treasury>,
+
+    #[account(
+        constraint = program.programdata_address()? == Some(program_data.key()) @ EscrowError::UnauthorizedAdmin
+    )]
+    pub program: Program<'info, crate::program::Escrow>,
+
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(admin.key()) @ EscrowError::UnauthorizedAdmin
+    )]
+    pub program_data: Account<'info, ProgramData>,
+    
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = config.admin == admin.key() @ EscrowError::UnauthorizedAdmin
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+    
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawTreasury<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = config.admin == admin.key() @ EscrowError::UnauthorizedAdmin
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"treasury"],
+        bump = treasury.bump
+    )]
+    pub treasury: Account<'info, Treasury>,
+    
+    pub admin: Signer<'info>,
+    
+    /// CHECK: Any account chosen by the admin to receive the fees
+    #[account(mut)]
+    pub recipient: AccountInfo<'info>,
+
+    /// Mint of the token fees to withdraw, omit for SOL fees
+    pub mint: Option<Box<InterfaceAccount<'info, Mint>>>,
+
+    /// Treasury's token account holding fees in `mint`
+    #[account(mut)]
+    pub treasury_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// Token account chosen by the admin to receive the token fees
+    #[account(mut)]
+    pub recipient_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+}
+
+#[derive(Accounts)]
+pub struct CreateArbiterPanel<'info> {
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"arbiter_panel", authority.key().as_ref()],
+        bump,
+        space = 8 + ArbiterPanel::INIT_SPACE
+    )]
+    pub arbiter_panel: Account<'info, ArbiterPanel>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetArbiterPanelApproval<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = config.admin == admin.key() @ EscrowError::UnauthorizedAdmin
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"arbiter_panel", arbiter_panel.authority.as_ref()],
+        bump = arbiter_panel.bump
+    )]
+    pub arbiter_panel: Account<'info, ArbiterPanel>,
+    
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(apartment_hash: [u8; 32], apartment_id: String, apartment_owner: Pubkey)]
+pub struct InitializeApartment<'info> {
+    #[account(
+        init,
+        payer = initializer,
+        seeds = [b"escrow", apartment_hash.as_ref()],
+        bump,
+        space = 8 + ApartmentEscrow::INIT_SPACE
+    )]
+    pub escrow_account: Account<'info, ApartmentEscrow>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+    
+    /// Panel that rules on disputed slashes for this apartment, must be approved by the config admin
+    #[account(
+        constraint = arbiter_panel.approved @ EscrowError::UnapprovedArbiterPanel
+    )]
+    pub arbiter_panel: Account<'info, ArbiterPanel>,
+    
+    #[account(mut)]
+    pub initializer: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+
+    /// Mint that stakes are paid in (e.g. USDC), omit for SOL stakes
+    pub stake_mint: Option<Box<InterfaceAccount<'info, Mint>>>,
+
+    /// Escrow's associated token account that holds token stakes
+    #[account(
+        init,
+        payer = initializer,
+        associated_token::mint = stake_mint,
+        associated_token::authority = escrow_account,
+        associated_token::token_program = token_program
+    )]
+    pub stake_vault: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+
+    pub associated_token_program: Option<Program<'info, AssociatedToken>>,
+}
+
+#[derive(Accounts)]
+#[instruction(apartment_hash: [u8; 32], amount: u64, profile_hash: [u8; 32], apartment_id: String, tenant_profile_id: String)]
+pub struct StakeForApartment<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", apartment_hash.as_ref()],
+        bump = escrow_account.bump
+    )]
+    pub escrow_account: Account<'info, ApartmentEscrow>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        init_if_needed,
+        payer = staker,
+        seeds = [b"stake", apartment_hash.as_ref(), profile_hash.as_ref()],
+        bump,
+        space = 8 + StakeRecord::INIT_SPACE
+    )]
+    pub stake_record: Account<'info, StakeRecord>,
+
+    /// Reputation the staker earned on this profile (optional), earns a discount on the required stake
+    #[account(
+        seeds = [b"reputation", profile_hash.as_ref(), staker.key().as_ref()],
+        bump = reputation.bump
+    )]
+    pub reputation: Option<Account<'info, Reputation>>,
+    
+    #[account(mut)]
+    pub staker: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+
+    /// Stake mint of token escrows, omit for SOL escrows
+    pub stake_mint: Option<Box<InterfaceAccount<'info, Mint>>>,
+
+    /// Escrow's associated token account holding token stakes
+    #[account(mut)]
+    pub stake_vault: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// Staker's token account for the stake mint
+    #[account(mut)]
+    pub staker_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+}
+
+#[derive(Accounts)]
+#[instruction(apartment_hash: [u8; 32], profile_hash: [u8; 32], apartment_id: String, tenant_profile_id: String)]
+pub struct ApproveTenant<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", apartment_hash.as_ref()],
+        bump = escrow_account.bump
+    )]
+    pub escrow_account: Account<'info, ApartmentEscrow>,
+
+    #[account(
+        seeds = [b"stake", apartment_hash.as_ref(), profile_hash.as_ref()],
+        bump = stake_record.bump,
+        constraint = stake_record.version == STAKE_RECORD_LAYOUT_VERSION @ EscrowError::StakeRecordNotMigrated
+    )]
+    pub stake_record: Account<'info, StakeRecord>,
+    
+    pub lessor: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(apartment_hash: [u8; 32], apartment_id: String)]
+pub struct UpdateLeasePhase<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", apartment_hash.as_ref()],
+        bump = escrow_account.bump
+    )]
+    pub escrow_account: Account<'info, ApartmentEscrow>,
+    
+    pub lessor: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(apartment_hash: [u8; 32], apartment_id: String, profile_hash: [u8; 32])]
+pub struct BeginMoveOut<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", apartment_hash.as_ref()],
+        bump = escrow_account.bump
+    )]
+    pub escrow_account: Account<'info, ApartmentEscrow>,
+
+    #[account(
+        seeds = [b"stake", apartment_hash.as_ref(), profile_hash.as_ref()],
+        bump = stake_record.bump,
+        constraint = stake_record.version == STAKE_RECORD_LAYOUT_VERSION @ EscrowError::StakeRecordNotMigrated
+    )]
+    pub stake_record: Account<'info, StakeRecord>,
+    
+    pub lessor: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(apartment_hash: [u8; 32], profile_hash: [u8; 32], apartment_id: String, tenant_profile_id: String)]
+pub struct ConvertToDeposit<'info> {
+    #[account(
+        seeds = [b"escrow", apartment_hash.as_ref()],
+        bump = escrow_account.bump
+    )]
+    pub escrow_account: Account<'info, ApartmentEscrow>,
+
+    #[account(
+        mut,
+        seeds = [b"stake", apartment_hash.as_ref(), profile_hash.as_ref()],
+        bump = stake_record.bump,
+        constraint = stake_record.version == STAKE_RECORD_LAYOUT_VERSION @ EscrowError::StakeRecordNotMigrated
+    )]
+    pub stake_record: Account<'info, StakeRecord>,
+    
+    pub lessor: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(apartment_hash: [u8; 32], profile_hash: [u8; 32])]
+pub struct SettleMoveOut<'info> {
+    #[account(
+        seeds = [b"escrow", apartment_hash.as_ref()],
+        bump = escrow_account.bump
+    )]
+    pub escrow_account: Account<'info, ApartmentEscrow>,
+
+    #[account(
+        seeds = [b"stake", apartment_hash.as_ref(), profile_hash.as_ref()],
+        bump = stake_record.bump,
+        constraint = stake_record.version == STAKE_RECORD_LAYOUT_VERSION @ EscrowError::StakeRecordNotMigrated
+    )]
+    pub stake_record: Account<'info, StakeRecord>,
+
+    #[account(
+        init,
+        payer = lessor,
+        seeds = [b"move_out", apartment_hash.as_ref(), profile_hash.as_ref()],
+        bump,
+        space = 8 + MoveOutSettlement::INIT_SPACE
+    )]
+    pub move_out_settlement: Account<'info, MoveOutSettlement>,
+    
+    #[account(mut)]
+    pub lessor: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(apartment_hash: [u8; 32], profile_hash: [u8; 32])]
+pub struct ReleaseDeposit<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", apartment_hash.as_ref()],
+        bump = escrow_account.bump
+    )]
+    pub escrow_account: Account<'info, ApartmentEscrow>,
+
+    #[account(
+        mut,
+        seeds = [b"stake", apartment_hash.as_ref(), profile_hash.as_ref()],
+        bump = stake_record.bump,
+        constraint = stake_record.version == STAKE_RECORD_LAYOUT_VERSION @ EscrowError::StakeRecordNotMigrated
+    )]
+    pub stake_record: Account<'info, StakeRecord>,
+
+    #[account(
+        mut,
+        seeds = [b"move_out", apartment_hash.as_ref(), profile_hash.as_ref()],
+        bump = move_out_settlement.bump,
+        close = rent_payer
+    )]
+    pub move_out_settlement: Account<'info, MoveOutSettlement>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+    
+    /// CHECK: This is the tenant who paid the deposit
+    #[account(
+        mut,
+        constraint = staker.key() == stake_record.staker @ EscrowError::UnauthorizedStaker
+    )]
+    pub staker: AccountInfo<'info>,
+    
+    /// CHECK: This is the current lessor, receives the deductions
+    #[account(
+        mut,
+        constraint = lessor.key() == escrow_account.lessor @ EscrowError::UnauthorizedLessor
+    )]
+    pub lessor: AccountInfo<'info>,
+    
+    /// CHECK: This is the account that paid rent for the settlement PDA
+    #[account(
+        mut,
+        constraint = rent_payer.key() == move_out_settlement.rent_payer @ EscrowError::InvalidRentPayer
+    )]
+    pub rent_payer: AccountInfo<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = caller,
+        seeds = [b"reputation", profile_hash.as_ref(), move_out_settlement.staker.as_ref()],
+        bump,
+        space = 8 + Reputation::INIT_SPACE
+    )]
+    pub reputation: Account<'info, Reputation>,
+    
+    #[account(mut)]
+    pub caller: Signer<'info>,
+
+    /// Stake mint of token escrows, omit for SOL escrows
+    pub stake_mint: Option<Box<InterfaceAccount<'info, Mint>>>,
+
+    /// Escrow's associated token account holding token stakes
+    #[account(mut)]
+    pub stake_vault: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// Staker's token account for the stake mint
+    #[account(mut)]
+    pub staker_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// Lessor's token account for the stake mint
+    #[account(mut)]
+    pub lessor_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(apartment_hash: [u8; 32], profile_hash: [u8; 32], apartment_id: String, tenant_profile_id: String, apartment_owner: Pubkey)]
+pub struct SlashStake<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", apartment_hash.as_ref()],
+        bump = escrow_account.bump
+    )]
+    pub escrow_account: Account<'info, ApartmentEscrow>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"stake", apartment_hash.as_ref(), profile_hash.as_ref()],
+        bump = stake_record.bump,
+        constraint = stake_record.version == STAKE_RECORD_LAYOUT_VERSION @ EscrowError::StakeRecordNotMigrated
+    )]
+    pub stake_record: Account<'info, StakeRecord>,
+
+    #[account(
+        init,
+        payer = lessor,
+        seeds = [b"dispute", apartment_hash.as_ref(), profile_hash.as_ref()],
+        bump,
+        space = 8 + SlashDispute::INIT_SPACE
+    )]
+    pub slash_dispute: Account<'info, SlashDispute>,
+
+    #[account(
+        init_if_needed,
+        payer = lessor,
+        seeds = [b"reputation", profile_hash.as_ref(), stake_record.staker.as_ref()],
+        bump,
+        space = 8 + Reputation::INIT_SPACE
+    )]
+    pub reputation: Account<'info, Reputation>,
+    
+    #[account(mut)]
+    pub lessor: Signer<'info>,
+    
+    /// CHECK: This is the original staker
+    #[account(
+        mut,
+        constraint = staker.key() == stake_record.staker @ EscrowError::UnauthorizedStaker
+    )]
+    pub staker: AccountInfo<'info>,
+    
+    /// CHECK: Referral PDA for this stake, may be uninitialized if nobody referred the tenant
+    #[account(
+        seeds = [b"referral", apartment_hash.as_ref(), profile_hash.as_ref()],
+        bump
+    )]
+    pub referral: UncheckedAccount<'info>,
+    
+    pub system_program: Program<'info, System>,
+
+    /// Stake mint of token escrows, omit for SOL escrows
+    pub stake_mint: Option<Box<InterfaceAccount<'info, Mint>>>,
+
+    /// Escrow's associated token account holding token stakes
+    #[account(mut)]
+    pub stake_vault: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// Staker's token account for the stake mint
+    #[account(mut)]
+    pub staker_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+}
+
+#[derive(Accounts)]
+#[instruction(apartment_hash: [u8; 32], profile_hash: [u8; 32])]
+pub struct DisputeSlash<'info> {
+    #[account(
+        mut,
+        seeds = [b"dispute", apartment_hash.as_ref(), profile_hash.as_ref()],
+        bump = slash_dispute.bump
+    )]
+    pub slash_dispute: Account<'info, SlashDispute>,
+    
+    pub staker: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(apartment_hash: [u8; 32], profile_hash: [u8; 32])]
+pub struct VoteOnDispute<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", apartment_hash.as_ref()],
+        bump = escrow_account.bump
+    )]
+    pub escrow_account: Account<'info, ApartmentEscrow>,
+
+    #[account(
+        mut,
+        seeds = [b"dispute", apartment_hash.as_ref(), profile_hash.as_ref()],
+        bump = slash_dispute.bump
+    )]
+    pub slash_dispute: Account<'info, SlashDispute>,
+
+    #[account(
+        mut,
+        seeds = [b"reputation", profile_hash.as_ref(), slash_dispute.staker.as_ref()],
+        bump = reputation.bump
+    )]
+    pub reputation: Account<'info, Reputation>,
+
+    #[account(
+        constraint = arbiter_panel.key() == escrow_account.arbiter_panel @ EscrowError::InvalidArbiterPanel,
+        constraint = arbiter_panel.approved @ EscrowError::UnapprovedArbiterPanel
+    )]
+    pub arbiter_panel: Account<'info, ArbiterPanel>,
+    
+    pub arbiter: Signer<'info>,
+    
+    /// CHECK: This is the original staker
+    #[account(
+        mut,
+        constraint = staker.key() == slash_dispute.staker @ EscrowError::UnauthorizedStaker
+    )]
+    pub staker: AccountInfo<'info>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+    
+    /// CHECK: This must be the penalty wallet from the protocol config, receives its share of slashes
+    #[account(
+        mut,
+        constraint = penalty_wallet.key() == config.penalty_wallet @ EscrowError::InvalidPenaltyWallet
+    )]
+    pub penalty_wallet: AccountInfo<'info>,
+    
+    /// CHECK: This is the current lessor, receives the lessor share
+    #[account(
+        mut,
+        constraint = lessor.key() == escrow_account.lessor @ EscrowError::UnauthorizedLessor
+    )]
+    pub lessor: AccountInfo<'info>,
+    
+    /// CHECK: Referral PDA for this stake, may be uninitialized if nobody referred the tenant
+    #[account(
+        seeds = [b"referral", apartment_hash.as_ref(), profile_hash.as_ref()],
+        bump
+    )]
+    pub referral: UncheckedAccount<'info>,
+    
+    /// CHECK: This is the referrer account (optional), must match the registered referral
+    #[account(mut)]
+    pub referrer: Option<AccountInfo<'info>>,
+    
+    /// CHECK: This is the account that paid rent for the dispute PDA
+    #[account(
+        mut,
+        constraint = rent_payer.key() == slash_dispute.rent_payer @ EscrowError::InvalidRentPayer
+    )]
+    pub rent_payer: AccountInfo<'info>,
+
+    /// Stake mint of token escrows, omit for SOL escrows
+    pub stake_mint: Option<Box<InterfaceAccount<'info, Mint>>>,
+
+    /// Escrow's associated token account holding token stakes
+    #[account(mut)]
+    pub stake_vault: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// Staker's token account for the stake mint
+    #[account(mut)]
+    pub staker_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// Lessor's token account for the stake mint
+    #[account(mut)]
+    pub lessor_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// Penalty wallet's token account for the stake mint
+    #[account(mut)]
+    pub penalty_wallet_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// Referrer's token account for the stake mint (optional)
+    #[account(mut)]
+    pub referrer_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+}
+
+#[derive(Accounts)]
+#[instruction(apartment_hash: [u8; 32], profile_hash: [u8; 32])]
+pub struct FinalizeSlash<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", apartment_hash.as_ref()],
+        bump = escrow_account.bump
+    )]
+    pub escrow_account: Account<'info, ApartmentEscrow>,
+
+    #[account(
+        mut,
+        seeds = [b"dispute", apartment_hash.as_ref(), profile_hash.as_ref()],
+        bump = slash_dispute.bump,
+        close = rent_payer
+    )]
+    pub slash_dispute: Account<'info, SlashDispute>,
+
+    #[account(
+        mut,
+        seeds = [b"reputation", profile_hash.as_ref(), slash_dispute.staker.as_ref()],
+        bump = reputation.bump
+    )]
+    pub reputation: Account<'info, Reputation>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+    
+    /// CHECK: This must be the penalty wallet from the protocol config, receives its share of slashes
+    #[account(
+        mut,
+        constraint = penalty_wallet.key() == config.penalty_wallet @ EscrowError::InvalidPenaltyWallet
+    )]
+    pub penalty_wallet: AccountInfo<'info>,
+    
+    /// CHECK: This is the current lessor, receives the lessor share
+    #[account(
+        mut,
+        constraint = lessor.key() == escrow_account.lessor @ EscrowError::UnauthorizedLessor
+    )]
+    pub lessor: AccountInfo<'info>,
+    
+    /// CHECK: Referral PDA for this stake, may be uninitialized if nobody referred the tenant
+    #[account(
+        seeds = [b"referral", apartment_hash.as_ref(), profile_hash.as_ref()],
+        bump
+    )]
+    pub referral: UncheckedAccount<'info>,
+    
+    /// CHECK: This is the referrer account (optional), must match the registered referral
+    #[account(mut)]
+    pub referrer: Option<AccountInfo<'info>>,
+    
+    /// CHECK: This is the account that paid rent for the dispute PDA
+    #[account(
+        mut,
+        constraint = rent_payer.key() == slash_dispute.rent_payer @ EscrowError::InvalidRentPayer
+    )]
+    pub rent_payer: AccountInfo<'info>,
+    
+    pub caller: Signer<'info>,
+
+    /// Stake mint of token escrows, omit for SOL escrows
+    pub stake_mint: Option<Box<InterfaceAccount<'info, Mint>>>,
+
+    /// Escrow's associated token account holding token stakes
+    #[account(mut)]
+    pub stake_vault: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// Lessor's token account for the stake mint
+    #[account(mut)]
+    pub lessor_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// Penalty wallet's token account for the stake mint
+    #[account(mut)]
+    pub penalty_wallet_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// Referrer's token account for the stake mint (optional)
+    #[account(mut)]
+    pub referrer_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+}
+
+#[derive(Accounts)]
+#[instruction(apartment_hash: [u8; 32], profile_hash: [u8; 32])]
+pub struct RefundUnruledDispute<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", apartment_hash.as_ref()],
+        bump = escrow_account.bump
+    )]
+    pub escrow_account: Account<'info, ApartmentEscrow>,
+
+    #[account(
+        mut,
+        seeds = [b"dispute", apartment_hash.as_ref(), profile_hash.as_ref()],
+        bump = slash_dispute.bump,
+        close = rent_payer
+    )]
+    pub slash_dispute: Account<'info, SlashDispute>,
+
+    /// CHECK: This is the original staker
+    #[account(
+        mut,
+        constraint = staker.key() == slash_dispute.staker @ EscrowError::UnauthorizedStaker
+    )]
+    pub staker: AccountInfo<'info>,
+
+    /// CHECK: This is the account that paid rent for the dispute PDA
+    #[account(
+        mut,
+        constraint = rent_payer.key() == slash_dispute.rent_payer @ EscrowError::InvalidRentPayer
+    )]
+    pub rent_payer: AccountInfo<'info>,
+
+    pub caller: Signer<'info>,
+
+    /// Stake mint of token escrows, omit for SOL escrows
+    pub stake_mint: Option<Box<InterfaceAccount<'info, Mint>>>,
+
+    /// Escrow's associated token account holding token stakes
+    #[account(mut)]
+    pub stake_vault: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// Staker's token account for the stake mint
+    #[account(mut)]
+    pub staker_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+}
+
+#[derive(Accounts)]
+#[instruction(apartment_hash: [u8; 32], profile_hash: [u8; 32], apartment_id: String, tenant_profile_id: String, apartment_owner: Pubkey)]
+pub struct ResolveStake<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", apartment_hash.as_ref()],
+        bump = escrow_account.bump
+    )]
+    pub escrow_account: Account<'info, ApartmentEscrow>,
+
+    #[account(
+        mut,
+        seeds = [b"stake", apartment_hash.as_ref(), profile_hash.as_ref()],
+        bump = stake_record.bump,
+        constraint = stake_record.version == STAKE_RECORD_LAYOUT_VERSION @ EscrowError::StakeRecordNotMigrated
+    )]
+    pub stake_record: Account<'info, StakeRecord>,
+
+    /// Staker's reputation, required once the approved tenant's lease ran, omit for other applicants
+    #[account(
+        init_if_needed,
+        payer = lessor,
+        seeds = [b"reputation", profile_hash.as_ref(), stake_record.staker.as_ref()],
+        bump,
+        space = 8 + Reputation::INIT_SPACE
+    )]
+    pub reputation: Option<Account<'info, Reputation>>,
+    
+    #[account(mut)]
+    pub lessor: Signer<'info>,
+    
+    /// CHECK: This is the original staker
+    #[account(mut)]
+    pub staker: AccountInfo<'info>,
+    
+    /// CHECK: Referral PDA for this stake, may be uninitialized if nobody referred the tenant
+    #[account(
+        seeds = [b"referral", apartment_hash.as_ref(), profile_hash.as_ref()],
+        bump
+    )]
+    pub referral: UncheckedAccount<'info>,
+    
+    /// CHECK: This is the referrer account (optional), must match the registered referral
+    #[account(mut)]
+    pub referrer: Option<AccountInfo<'info>>,
+
+    /// Paid-referral counter of the registered referrer (optional)
+    #[account(
+        mut,
+        seeds = [b"referrer_count", apartment_hash.as_ref(), referrer_counter.referrer.as_ref()],
+        bump = referrer_counter.bump
+    )]
+    pub referrer_counter: Option<Account<'info, ReferrerCounter>>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"treasury"],
+        bump = treasury.bump
+    )]
+    pub treasury: Account<'info, Treasury>,
+
+    /// Stake mint of token escrows, omit for SOL escrows
+    pub stake_mint: Option<Box<InterfaceAccount<'info, Mint>>>,
+
+    /// Escrow's associated token account holding token stakes
+    #[account(mut)]
+    pub stake_vault: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// Staker's token account for the stake mint
+    #[account(mut)]
+    pub staker_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
 
-        stake_record.is_active = false;
-        
-        // Update stake record to reflect what was actually transferred
-        stake_record.amount = stake_record_amount
-            .checked_sub(total_transfer_amount)
-            .ok_or(EscrowError::ArithmeticOverflow)?;
+    /// Referrer's token account for the stake mint (optional)
+    #[account(mut)]
+    pub referrer_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
 
-        // Update total staked in escrow
-        escrow_account.total_staked = escrow_total_staked
-            .checked_sub(total_transfer_amount)
-            .ok_or(EscrowError::InsufficientFunds)?;
+    /// Treasury's token account for the stake mint
+    #[account(mut)]
+    pub treasury_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
 
-        msg!("Successfully updated total_staked to: {}", escrow_account.total_staked);
-        msg!("Stake record updated to: {}", stake_record.amount);
+    pub token_program: Option<Interface<'info, TokenInterface>>,
 
-        // Transfer referrer reward if applicable
-        if referrer_reward > 0 && referrer_pubkey.is_some() {
-            let referrer_account = &ctx.accounts.referrer.as_ref()
-                .ok_or(EscrowError::MissingReferrerAccount)?;
-            
-            **ctx.accounts.escrow_account.to_account_info().try_borrow_mut_lamports()? -= referrer_reward;
-            **referrer_account.to_account_info().try_borrow_mut_lamports()? += referrer_reward;
-            
-            msg!("Transferred {} to referrer: {}", referrer_reward, referrer_pubkey.unwrap());
-        }
+    pub system_program: Program<'info, System>,
+}
 
-        // Transfer remaining amount to original staker
-        if staker_amount > 0 {
-            **ctx.accounts.escrow_account.to_account_info().try_borrow_mut_lamports()? -= staker_amount;
-            **ctx.accounts.staker.to_account_info().try_borrow_mut_lamports()? += staker_amount;
-            
-            msg!("Transferred {} to staker: {}", staker_amount, staker);
-        }
+#[derive(Accounts)]
+#[instruction(apartment_hash: [u8; 32], profile_hash: [u8; 32])]
+pub struct MutualRelease<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", apartment_hash.as_ref()],
+        bump = escrow_account.bump
+    )]
+    pub escrow_account: Account<'info, ApartmentEscrow>,
 
-        msg!("Transfer completed successfully");
+    #[account(
+        mut,
+        seeds = [b"stake", apartment_hash.as_ref(), profile_hash.as_ref()],
+        bump = stake_record.bump,
+        constraint = stake_record.version == STAKE_RECORD_LAYOUT_VERSION @ EscrowError::StakeRecordNotMigrated
+    )]
+    pub stake_record: Account<'info, StakeRecord>,
+    
+    #[account(
+        mut,
+        constraint = lessor.key() == escrow_account.lessor @ EscrowError::UnauthorizedLessor
+    )]
+    pub lessor: Signer<'info>,
+    
+    #[account(
+        mut,
+        constraint = staker.key() == stake_record.staker @ EscrowError::UnauthorizedStaker
+    )]
+    pub staker: Signer<'info>,
+    
+    /// CHECK: Referral PDA for this stake, may be uninitialized if nobody referred the tenant
+    #[account(
+        seeds = [b"referral", apartment_hash.as_ref(), profile_hash.as_ref()],
+        bump
+    )]
+    pub referral: UncheckedAccount<'info>,
+    
+    /// CHECK: This is the referrer account (optional), must match the registered referral
+    #[account(mut)]
+    pub referrer: Option<AccountInfo<'info>>,
 
-        emit!(StakeResolved {
-            tenant_profile_id,
-            apartment_id,
-            staker,
-            amount: total_transfer_amount,
-            referrer_reward,
-            referrer: referrer_pubkey,
-        });
+    /// Paid-referral counter of the registered referrer (optional)
+    #[account(
+        mut,
+        seeds = [b"referrer_count", apartment_hash.as_ref(), referrer_counter.referrer.as_ref()],
+        bump = referrer_counter.bump
+    )]
+    pub referrer_counter: Option<Account<'info, ReferrerCounter>>,
 
-        Ok(())
-    }
+    /// CHECK: Move-out settlement PDA for this stake, must not exist
+    #[account(
+        seeds = [b"move_out", apartment_hash.as_ref(), profile_hash.as_ref()],
+        bump
+    )]
+    pub move_out_settlement: UncheckedAccount<'info>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, ProtocolConfig>,
 
-    /// Close escrow (lessor action - when rental period ends)
-    pub fn close_escrow(ctx: Context<CloseEscrow>, apartment_hash: [u8; 32], apartment_id: String) -> Result<()> {
-        let escrow_account = &mut ctx.accounts.escrow_account;
+    /// Stake mint of token escrows, omit for SOL escrows
+    pub stake_mint: Option<Box<InterfaceAccount<'info, Mint>>>,
 
-        require!(escrow_account.is_active, EscrowError::EscrowNotActive);
-        require!(escrow_account.apartment_id == apartment_id, EscrowError::InvalidApartment);
-        require!(ctx.accounts.lessor.key() == escrow_account.lessor, EscrowError::UnauthorizedLessor);
+    /// Escrow's associated token account holding token stakes
+    #[account(mut)]
+    pub stake_vault: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
 
-        // TODO: Check if there are any active stakes before closing
-        escrow_account.is_active = false;
+    /// Staker's token account for the stake mint
+    #[account(mut)]
+    pub staker_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
 
-        emit!(EscrowClosed {
-            apartment_id,
-            lessor: ctx.accounts.lessor.key(),
-        });
+    /// Lessor's token account for the stake mint
+    #[account(mut)]
+    pub lessor_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
 
-        Ok(())
-    }
-}
+    /// Referrer's token account for the stake mint (optional)
+    #[account(mut)]
+    pub referrer_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
 
-// ============================================================================
-// ACCOUNT CONTEXTS
-// ============================================================================
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+}
 
 #[derive(Accounts)]
-#[instruction(apartment_hash: [u8; 32], apartment_id: String, apartment_owner: Pubkey)]
-pub struct InitializeApartment<'info> {
+#[instruction(apartment_hash: [u8; 32], profile_hash: [u8; 32], apartment_id: String, tenant_profile_id: String)]
+pub struct RegisterReferral<'info> {
     #[account(
-        init,
-        payer = initializer,
         seeds = [b"escrow", apartment_hash.as_ref()],
-        bump,
-        space = 8 + ApartmentEscrow::INIT_SPACE
+        bump = escrow_account.bump
     )]
     pub escrow_account: Account<'info, ApartmentEscrow>,
+
+    #[account(
+        init,
+        payer = referrer,
+        seeds = [b"referral", apartment_hash.as_ref(), profile_hash.as_ref()],
+        bump,
+        space = 8 + Referral::INIT_SPACE
+    )]
+    pub referral: Account<'info, Referral>,
+
+    #[account(
+        init_if_needed,
+        payer = referrer,
+        seeds = [b"referrer_count", apartment_hash.as_ref(), referrer.key().as_ref()],
+        bump,
+        space = 8 + ReferrerCounter::INIT_SPACE
+    )]
+    pub referrer_counter: Account<'info, ReferrerCounter>,
+    
+    /// CHECK: Stake record PDA for this profile, must not exist yet
+    #[account(
+        seeds = [b"stake", apartment_hash.as_ref(), profile_hash.as_ref()],
+        bump
+    )]
+    pub stake_record: UncheckedAccount<'info>,
     
     #[account(mut)]
-    pub initializer: Signer<'info>,
+    pub referrer: Signer<'info>,
+
+    /// CHECK: Wallet the tenant will stake from, its consent signature is checked in the handler
+    pub tenant: UncheckedAccount<'info>,
+
+    /// CHECK: Instructions sysvar, holds the ed25519 instruction with the tenant's consent
+    #[account(address = solana_instructions_sysvar::ID)]
+    pub instructions: UncheckedAccount<'info>,
     
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-#[instruction(apartment_hash: [u8; 32], amount: u64, profile_hash: [u8; 32], apartment_id: String, tenant_profile_id: String)]
-pub struct StakeForApartment<'info> {
+#[instruction(apartment_hash: [u8; 32], apartment_id: String)]
+pub struct RefundApplicants<'info> {
     #[account(
         mut,
         seeds = [b"escrow", apartment_hash.as_ref()],
@@ -355,23 +3643,61 @@ pub struct StakeForApartment<'info> {
     pub escrow_account: Account<'info, ApartmentEscrow>,
 
     #[account(
-        init_if_needed,
-        payer = staker,
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+    
+    pub lessor: Signer<'info>,
+
+    /// Stake mint of token escrows, omit for SOL escrows
+    pub stake_mint: Option<Box<InterfaceAccount<'info, Mint>>>,
+
+    /// Escrow's associated token account holding token stakes
+    #[account(mut)]
+    pub stake_vault: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+}
+
+#[derive(Accounts)]
+#[instruction(apartment_hash: [u8; 32], profile_hash: [u8; 32], apartment_id: String, tenant_profile_id: String)]
+pub struct WithdrawStake<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", apartment_hash.as_ref()],
+        bump = escrow_account.bump
+    )]
+    pub escrow_account: Account<'info, ApartmentEscrow>,
+
+    #[account(
+        mut,
         seeds = [b"stake", apartment_hash.as_ref(), profile_hash.as_ref()],
-        bump,
-        space = 8 + StakeRecord::INIT_SPACE
+        bump = stake_record.bump,
+        constraint = stake_record.version == STAKE_RECORD_LAYOUT_VERSION @ EscrowError::StakeRecordNotMigrated
     )]
     pub stake_record: Account<'info, StakeRecord>,
     
     #[account(mut)]
     pub staker: Signer<'info>,
-    
-    pub system_program: Program<'info, System>,
+
+    /// Stake mint of token escrows, omit for SOL escrows
+    pub stake_mint: Option<Box<InterfaceAccount<'info, Mint>>>,
+
+    /// Escrow's associated token account holding token stakes
+    #[account(mut)]
+    pub stake_vault: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// Staker's token account for the stake mint
+    #[account(mut)]
+    pub staker_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    pub token_program: Option<Interface<'info, TokenInterface>>,
 }
 
 #[derive(Accounts)]
-#[instruction(apartment_hash: [u8; 32], profile_hash: [u8; 32], apartment_id: String, tenant_profile_id: String, apartment_owner: Pubkey)]
-pub struct SlashStake<'info> {
+#[instruction(apartment_hash: [u8; 32], profile_hash: [u8; 32], apartment_id: String, tenant_profile_id: String)]
+pub struct RefundExpiredStake<'info> {
     #[account(
         mut,
         seeds = [b"escrow", apartment_hash.as_ref()],
@@ -382,23 +3708,37 @@ pub struct SlashStake<'info> {
     #[account(
         mut,
         seeds = [b"stake", apartment_hash.as_ref(), profile_hash.as_ref()],
-        bump = stake_record.bump
+        bump = stake_record.bump,
+        constraint = stake_record.version == STAKE_RECORD_LAYOUT_VERSION @ EscrowError::StakeRecordNotMigrated
     )]
     pub stake_record: Account<'info, StakeRecord>,
     
-    pub lessor: Signer<'info>,
-    
-    /// CHECK: This must be the hardcoded penalty wallet
+    /// CHECK: This is the original staker
     #[account(
         mut,
-        constraint = penalty_wallet.key() == get_penalty_wallet() @ EscrowError::InvalidPenaltyWallet
+        constraint = staker.key() == stake_record.staker @ EscrowError::UnauthorizedStaker
     )]
-    pub penalty_wallet: AccountInfo<'info>,
+    pub staker: AccountInfo<'info>,
+    
+    pub caller: Signer<'info>,
+
+    /// Stake mint of token escrows, omit for SOL escrows
+    pub stake_mint: Option<Box<InterfaceAccount<'info, Mint>>>,
+
+    /// Escrow's associated token account holding token stakes
+    #[account(mut)]
+    pub stake_vault: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// Staker's token account for the stake mint
+    #[account(mut)]
+    pub staker_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    pub token_program: Option<Interface<'info, TokenInterface>>,
 }
 
 #[derive(Accounts)]
-#[instruction(apartment_hash: [u8; 32], profile_hash: [u8; 32], apartment_id: String, tenant_profile_id: String, apartment_owner: Pubkey, referrer_pubkey: Option<Pubkey>, reward_amount: u64)]
-pub struct ResolveStake<'info> {
+#[instruction(apartment_hash: [u8; 32], profile_hash: [u8; 32])]
+pub struct RefundUnsettledDeposit<'info> {
     #[account(
         mut,
         seeds = [b"escrow", apartment_hash.as_ref()],
@@ -409,19 +3749,125 @@ pub struct ResolveStake<'info> {
     #[account(
         mut,
         seeds = [b"stake", apartment_hash.as_ref(), profile_hash.as_ref()],
-        bump = stake_record.bump
+        bump = stake_record.bump,
+        constraint = stake_record.version == STAKE_RECORD_LAYOUT_VERSION @ EscrowError::StakeRecordNotMigrated
+    )]
+    pub stake_record: Account<'info, StakeRecord>,
+
+    /// CHECK: Move-out settlement PDA for this stake, must not exist
+    #[account(
+        seeds = [b"move_out", apartment_hash.as_ref(), profile_hash.as_ref()],
+        bump
+    )]
+    pub move_out_settlement: UncheckedAccount<'info>,
+
+    /// CHECK: This is the tenant who paid the deposit
+    #[account(
+        mut,
+        constraint = staker.key() == stake_record.staker @ EscrowError::UnauthorizedStaker
+    )]
+    pub staker: AccountInfo<'info>,
+
+    pub caller: Signer<'info>,
+
+    /// Stake mint of token escrows, omit for SOL escrows
+    pub stake_mint: Option<Box<InterfaceAccount<'info, Mint>>>,
+
+    /// Escrow's associated token account holding token stakes
+    #[account(mut)]
+    pub stake_vault: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// Staker's token account for the stake mint
+    #[account(mut)]
+    pub staker_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+}
+
+#[derive(Accounts)]
+#[instruction(apartment_hash: [u8; 32], profile_hash: [u8; 32], apartment_id: String, tenant_profile_id: String)]
+pub struct CloseStakeRecord<'info> {
+    #[account(
+        mut,
+        seeds = [b"stake", apartment_hash.as_ref(), profile_hash.as_ref()],
+        bump = stake_record.bump,
+        constraint = stake_record.version == STAKE_RECORD_LAYOUT_VERSION @ EscrowError::StakeRecordNotMigrated,
+        close = staker
     )]
     pub stake_record: Account<'info, StakeRecord>,
+
+    /// CHECK: Escrow PDA of the listing, may already be closed
+    #[account(
+        seeds = [b"escrow", apartment_hash.as_ref()],
+        bump
+    )]
+    pub escrow_account: UncheckedAccount<'info>,
+    
+    #[account(
+        mut,
+        constraint = staker.key() == stake_record.staker @ EscrowError::UnauthorizedStaker
+    )]
+    pub staker: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(apartment_hash: [u8; 32], apartment_id: String, new_lessor: Pubkey)]
+pub struct ProposeLessorTransfer<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", apartment_hash.as_ref()],
+        bump = escrow_account.bump
+    )]
+    pub escrow_account: Account<'info, ApartmentEscrow>,
     
     pub lessor: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(apartment_hash: [u8; 32], apartment_id: String)]
+pub struct CancelLessorTransfer<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", apartment_hash.as_ref()],
+        bump = escrow_account.bump
+    )]
+    pub escrow_account: Account<'info, ApartmentEscrow>,
     
-    /// CHECK: This is the original staker
+    pub lessor: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(apartment_hash: [u8; 32], apartment_id: String)]
+pub struct AcceptLessorTransfer<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", apartment_hash.as_ref()],
+        bump = escrow_account.bump
+    )]
+    pub escrow_account: Account<'info, ApartmentEscrow>,
+    
+    pub new_lessor: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateAccount<'info> {
+    /// CHECK: ApartmentEscrow or StakeRecord in any layout version, validated in the handler
     #[account(mut)]
-    pub staker: AccountInfo<'info>,
+    pub account: UncheckedAccount<'info>,
     
-    /// CHECK: This is the referrer account (optional)
     #[account(mut)]
-    pub referrer: Option<AccountInfo<'info>>,
+    pub payer: Signer<'info>,
+
+    /// Lessor of a legacy escrow, signs for the terms it supplies (optional)
+    pub lessor: Option<Signer<'info>>,
+
+    /// Panel for a legacy escrow that never had one, must be approved by the config admin (optional)
+    #[account(
+        constraint = arbiter_panel.approved @ EscrowError::UnapprovedArbiterPanel
+    )]
+    pub arbiter_panel: Option<Account<'info, ArbiterPanel>>,
+    
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
@@ -430,16 +3876,54 @@ pub struct CloseEscrow<'info> {
     #[account(
         mut,
         seeds = [b"escrow", apartment_hash.as_ref()],
-        bump = escrow_account.bump
+        bump = escrow_account.bump,
+        close = rent_payer
     )]
     pub escrow_account: Account<'info, ApartmentEscrow>,
     
     pub lessor: Signer<'info>,
+    
+    /// CHECK: This is the account that paid rent for the escrow PDA
+    #[account(
+        mut,
+        constraint = rent_payer.key() == escrow_account.rent_payer @ EscrowError::InvalidRentPayer
+    )]
+    pub rent_payer: AccountInfo<'info>,
+
+    /// Stake mint of token escrows, omit for SOL escrows
+    pub stake_mint: Option<Box<InterfaceAccount<'info, Mint>>>,
+
+    /// Escrow's associated token account holding token stakes
+    #[account(mut)]
+    pub stake_vault: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// Lessor's token account for the stake mint
+    #[account(mut)]
+    pub lessor_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+}
+
+// ============================================================================
+// ACCOUNT STRUCTS
+// ============================================================================
+
+#[account]
+#[derive(InitSpace)]
+pub struct ProtocolConfig {
+    pub admin: Pubkey,
+    pub penalty_wallet: Pubkey,
+    pub bump: u8,
+    pub resolution_fee_bps: u16,
+    pub paused: bool,
+    pub payouts_paused: bool,
 }
 
-// ============================================================================
-// ACCOUNT STRUCTS
-// ============================================================================
+#[account]
+#[derive(InitSpace)]
+pub struct Treasury {
+    pub bump: u8,
+}
 
 #[account]
 #[derive(InitSpace)]
@@ -448,8 +3932,25 @@ pub struct ApartmentEscrow {
     pub apartment_id: String,
     pub lessor: Pubkey,
     pub total_staked: u64,
-    pub is_active: bool,
+    pub status: EscrowStatus,
     pub bump: u8,
+    pub decision_deadline: i64,
+    pub active_stakes: u32,
+    pub rent_payer: Pubkey,
+    pub pending_lessor: Option<Pubkey>,
+    pub required_stake: u64,
+    pub referral_reward: u64,
+    pub referral_limit: u32,
+    pub approved_profile: Option<[u8; 32]>,
+    pub arbiter_panel: Pubkey,
+    pub dispute_window: i64,
+    pub pending_slashes: u32,
+    pub slash_split: SlashSplit,
+    pub version: u8,
+    pub stake_mint: Option<Pubkey>,
+    pub stake_discount: StakeDiscount,
+    /// When start_lease ran, zero before the lease starts
+    pub lease_started_at: i64,
 }
 
 #[account]
@@ -461,8 +3962,237 @@ pub struct StakeRecord {
     pub apartment_id: String,
     pub staker: Pubkey,
     pub amount: u64,
-    pub is_active: bool,
+    pub status: StakeStatus,
+    pub bump: u8,
+    pub version: u8,
+    /// Deposits only, move-out must be settled within one dispute window after this
+    pub lease_end: i64,
+}
+
+// Fields shared by every StakeRecord layout, used to read records that are not migrated yet
+#[derive(AnchorDeserialize)]
+struct StakeRecordPrefix {
+    tenant_profile_id: String,
+    apartment_id: String,
+    staker: Pubkey,
+    amount: u64,
+    status: StakeStatus,
+    bump: u8,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Referral {
+    #[max_len(50)]
+    pub tenant_profile_id: String,
+    #[max_len(50)]
+    pub apartment_id: String,
+    pub referrer: Pubkey,
+    pub bump: u8,
+    pub tenant: Pubkey,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct ReferrerCounter {
+    #[max_len(50)]
+    pub apartment_id: String,
+    pub referrer: Pubkey,
+    pub referrals_paid: u32,
+    pub bump: u8,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct SlashDispute {
+    #[max_len(50)]
+    pub tenant_profile_id: String,
+    #[max_len(50)]
+    pub apartment_id: String,
+    pub staker: Pubkey,
+    pub amount: u64,
+    pub reason: SlashReason,
+    pub dispute_deadline: i64,
+    /// Once disputed, the arbiter panel must rule by this time or the staker is refunded
+    pub ruling_deadline: i64,
+    pub status: DisputeStatus,
+    pub rent_payer: Pubkey,
+    pub bump: u8,
+    #[max_len(MAX_ARBITERS)]
+    pub votes: Vec<ArbiterVote>,
+    #[max_len(MAX_DEDUCTIONS)]
+    pub line_items: Vec<DeductionItem>,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct MoveOutSettlement {
+    #[max_len(50)]
+    pub tenant_profile_id: String,
+    #[max_len(50)]
+    pub apartment_id: String,
+    pub staker: Pubkey,
+    pub deposit_amount: u64,
+    pub total_deductions: u64,
+    pub response_deadline: i64,
+    pub rent_payer: Pubkey,
+    pub bump: u8,
+    #[max_len(MAX_DEDUCTIONS)]
+    pub deductions: Vec<DeductionItem>,
+}
+
+/// Stake outcomes for a tenant profile staked from one wallet, written only by this program
+/// Seeds: [b"reputation", profile_hash, owner]
+#[account]
+#[derive(InitSpace)]
+pub struct Reputation {
+    pub profile_hash: [u8; 32],
+    /// Staker wallet whose outcomes this account records
+    pub owner: Pubkey,
+    /// Leases completed as the approved tenant, by a resolved stake or a released deposit
+    pub completed_stakes: u32,
+    /// Lamports held through completed SOL stakes and deposits
+    pub total_resolved: u64,
+    /// Slashes that stood after the dispute window or the panel's ruling
+    pub slash_count: u32,
+    /// Lamports slashed from SOL stakes
+    pub total_slashed: u64,
+    pub bump: u8,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct ArbiterPanel {
+    pub authority: Pubkey,
+    #[max_len(MAX_ARBITERS)]
+    pub arbiters: Vec<Pubkey>,
+    pub threshold: u8,
     pub bump: u8,
+    /// Set by the config admin, only approved panels can be assigned to new escrows
+    pub approved: bool,
+}
+
+// ============================================================================
+// TYPES
+// ============================================================================
+
+/// How much of a stake to slash
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SlashAmount {
+    /// Fixed amount in the escrow's stake asset (lamports or token base units)
+    Amount(u64),
+    /// Share of the stake in basis points (10_000 = 100%)
+    BasisPoints(u16),
+}
+
+/// Why a stake was slashed
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum SlashReason {
+    MissedViewing,
+    NoShow,
+    FalseInformation,
+    AgreementBreach,
+    PropertyDamage,
+    Other,
+}
+
+/// How slashed funds are divided, in basis points (must total 10_000)
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub struct SlashSplit {
+    pub lessor_bps: u16,
+    pub penalty_wallet_bps: u16,
+    pub referrer_bps: u16,
+}
+
+/// What migrate_account needs to bring an escrow from before layout versioning up to date
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct LegacyEscrowMigration {
+    pub apartment_hash: [u8; 32],
+    /// Profile hash of each stake record in remaining_accounts, in the same order
+    pub profile_hashes: Vec<[u8; 32]>,
+    /// Only used when the lessor signs and the escrow has no decision deadline, counted from the migration
+    pub decision_window: i64,
+    /// Only used when the lessor signs and the escrow has no dispute window
+    pub dispute_window: i64,
+    /// Only used when the lessor signs and the escrow has no slash split
+    pub slash_split: SlashSplit,
+}
+
+/// Reputation discount on the required stake, zero for no discount
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq, InitSpace)]
+pub struct StakeDiscount {
+    /// Discount earned per completed stake of at least the required stake, in basis points
+    pub bps_per_completed_stake: u16,
+    /// Cap on the total discount, in basis points
+    pub max_discount_bps: u16,
+}
+
+/// A panel member's vote on how to split a disputed slash
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub struct ArbiterVote {
+    pub arbiter: Pubkey,
+    /// Share of the held amount returned to the staker
+    pub tenant_share_bps: u16,
+}
+
+/// Lifecycle phase of an apartment escrow
+/// Closed and Open come first so bytes written by the old `is_active` flag decode unchanged
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum EscrowStatus {
+    /// Escrow is closed
+    Closed,
+    /// Listing open, taking stakes and referrals
+    Open,
+    /// Tenant approved, waiting for the lease to start
+    TenantApproved,
+    /// Lease is running
+    LeaseActive,
+    /// Lease ended, move-out under review
+    MoveOutReview,
+}
+
+/// Whether a stake record still holds funds in the escrow
+/// Same byte values as the old `is_active` flag
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum StakeStatus {
+    /// Paid out, refunded or slashed
+    Settled,
+    /// Held in the escrow
+    Active,
+    /// Approved tenant's stake held as the lease security deposit
+    Deposit,
+}
+
+/// One itemized line of a slash, resolution or deposit deduction
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq, InitSpace)]
+pub struct DeductionItem {
+    pub category: DeductionCategory,
+    pub amount: u64,
+    /// Hash of the photos, invoices or other evidence backing the deduction
+    pub evidence_hash: [u8; 32],
+    /// Where the evidence can be viewed
+    #[max_len(MAX_EVIDENCE_URI_LEN)]
+    pub uri: String,
+}
+
+/// What a deduction line item charges for
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum DeductionCategory {
+    Damage,
+    Cleaning,
+    UnpaidRent,
+    Utilities,
+    MissedAppointment,
+    Other,
+}
+
+/// Where a held slash is in its dispute window
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum DisputeStatus {
+    /// Window open, staker has not disputed
+    Pending,
+    /// Staker disputed, waiting for the arbiter panel
+    Disputed,
 }
 
 // ============================================================================
@@ -477,12 +4207,73 @@ pub struct StakeCreated {
     pub amount: u64,
 }
 
+#[event]
+pub struct TenantApproved {
+    pub tenant_profile_id: String,
+    pub apartment_id: String,
+    pub profile_hash: [u8; 32],
+    pub staker: Pubkey,
+}
+
 #[event]
 pub struct StakeSlashed {
     pub tenant_profile_id: String,
     pub apartment_id: String,
     pub staker: Pubkey,
     pub amount: u64,
+    pub refunded_amount: u64,
+    pub reason: SlashReason,
+    pub dispute_deadline: i64,
+    pub lessor_share: u64,
+    pub penalty_wallet_share: u64,
+    pub referrer_share: u64,
+    pub referrer: Option<Pubkey>,
+    pub line_items: Vec<DeductionItem>,
+}
+
+#[event]
+pub struct SlashDisputed {
+    pub tenant_profile_id: String,
+    pub apartment_id: String,
+    pub staker: Pubkey,
+    pub amount: u64,
+    pub ruling_deadline: i64,
+}
+
+#[event]
+pub struct DisputeVoteCast {
+    pub tenant_profile_id: String,
+    pub apartment_id: String,
+    pub arbiter: Pubkey,
+    pub tenant_share_bps: u16,
+    pub matching_votes: u8,
+    pub threshold: u8,
+}
+
+#[event]
+pub struct DisputeRuled {
+    pub tenant_profile_id: String,
+    pub apartment_id: String,
+    pub staker: Pubkey,
+    pub arbiter_panel: Pubkey,
+    pub tenant_amount: u64,
+    pub penalty_amount: u64,
+    pub lessor_amount: u64,
+    pub penalty_wallet_amount: u64,
+    pub referrer_amount: u64,
+    pub referrer: Option<Pubkey>,
+}
+
+#[event]
+pub struct SlashFinalized {
+    pub tenant_profile_id: String,
+    pub apartment_id: String,
+    pub staker: Pubkey,
+    pub amount: u64,
+    pub lessor_amount: u64,
+    pub penalty_wallet_amount: u64,
+    pub referrer_amount: u64,
+    pub referrer: Option<Pubkey>,
 }
 
 #[event]
@@ -493,24 +4284,226 @@ pub struct StakeResolved {
     pub amount: u64,
     pub referrer_reward: u64,
     pub referrer: Option<Pubkey>,
+    pub protocol_fee: u64,
+    pub line_items: Vec<DeductionItem>,
+}
+
+#[event]
+pub struct StakeConvertedToDeposit {
+    pub tenant_profile_id: String,
+    pub apartment_id: String,
+    pub staker: Pubkey,
+    pub amount: u64,
+    pub lease_end: i64,
+}
+
+#[event]
+pub struct MoveOutProposed {
+    pub tenant_profile_id: String,
+    pub apartment_id: String,
+    pub staker: Pubkey,
+    pub deposit_amount: u64,
+    pub total_deductions: u64,
+    pub deductions: Vec<DeductionItem>,
+    pub response_deadline: i64,
+}
+
+#[event]
+pub struct DepositReleased {
+    pub tenant_profile_id: String,
+    pub apartment_id: String,
+    pub staker: Pubkey,
+    pub deducted_amount: u64,
+    pub refunded_amount: u64,
+    pub accepted_by_tenant: bool,
+}
+
+#[event]
+pub struct MutualReleased {
+    pub tenant_profile_id: String,
+    pub apartment_id: String,
+    pub staker: Pubkey,
+    pub lessor: Pubkey,
+    pub lessor_amount: u64,
+    pub referrer_amount: u64,
+    pub staker_amount: u64,
+    pub referrer: Option<Pubkey>,
+}
+
+#[event]
+pub struct ReferralRegistered {
+    pub tenant_profile_id: String,
+    pub apartment_id: String,
+    pub referrer: Pubkey,
+    pub tenant: Pubkey,
+}
+
+#[event]
+pub struct ApplicantsRefunded {
+    pub apartment_id: String,
+    pub refunded_count: u32,
+    pub total_refunded: u64,
+}
+
+#[event]
+pub struct StakeWithdrawn {
+    pub tenant_profile_id: String,
+    pub apartment_id: String,
+    pub staker: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct UnruledDisputeRefunded {
+    pub tenant_profile_id: String,
+    pub apartment_id: String,
+    pub staker: Pubkey,
+    pub amount: u64,
+    pub ruling_deadline: i64,
+}
+
+#[event]
+pub struct UnsettledDepositRefunded {
+    pub tenant_profile_id: String,
+    pub apartment_id: String,
+    pub staker: Pubkey,
+    pub amount: u64,
+    pub lease_end: i64,
+}
+
+#[event]
+pub struct ExpiredStakeRefunded {
+    pub tenant_profile_id: String,
+    pub apartment_id: String,
+    pub staker: Pubkey,
+    pub amount: u64,
+    pub decision_deadline: i64,
+}
+
+#[event]
+pub struct StakeRecordClosed {
+    pub tenant_profile_id: String,
+    pub apartment_id: String,
+    pub staker: Pubkey,
+}
+
+#[event]
+pub struct LessorTransferProposed {
+    pub apartment_id: String,
+    pub current_lessor: Pubkey,
+    pub proposed_lessor: Pubkey,
+}
+
+#[event]
+pub struct LessorTransferCancelled {
+    pub apartment_id: String,
+    pub lessor: Pubkey,
+    pub cancelled_lessor: Pubkey,
+}
+
+#[event]
+pub struct LessorTransferAccepted {
+    pub apartment_id: String,
+    pub previous_lessor: Pubkey,
+    pub new_lessor: Pubkey,
+}
+
+#[event]
+pub struct AccountMigrated {
+    pub account: Pubkey,
+    pub previous_version: u8,
+    pub new_version: u8,
+    pub payer: Pubkey,
+}
+
+#[event]
+pub struct EscrowStatusChanged {
+    pub apartment_id: String,
+    pub previous_status: EscrowStatus,
+    pub new_status: EscrowStatus,
 }
 
 #[event]
 pub struct EscrowClosed {
     pub apartment_id: String,
     pub lessor: Pubkey,
+    pub rent_payer: Pubkey,
+}
+
+#[event]
+pub struct ConfigInitialized {
+    pub admin: Pubkey,
+    pub penalty_wallet: Pubkey,
+    pub resolution_fee_bps: u16,
+}
+
+#[event]
+pub struct ResolutionFeeUpdated {
+    pub previous_fee_bps: u16,
+    pub new_fee_bps: u16,
+}
+
+#[event]
+pub struct PauseUpdated {
+    pub paused: bool,
+    pub payouts_paused: bool,
+    pub admin: Pubkey,
+}
+
+#[event]
+pub struct TreasuryWithdrawn {
+    pub recipient: Pubkey,
+    pub amount: u64,
+    pub mint: Option<Pubkey>,
+}
+
+#[event]
+pub struct PenaltyWalletUpdated {
+    pub previous_penalty_wallet: Pubkey,
+    pub new_penalty_wallet: Pubkey,
+}
+
+#[event]
+pub struct ConfigAdminUpdated {
+    pub previous_admin: Pubkey,
+    pub new_admin: Pubkey,
+}
+
+#[event]
+pub struct ArbiterPanelCreated {
+    pub arbiter_panel: Pubkey,
+    pub authority: Pubkey,
+    pub arbiters: Vec<Pubkey>,
+    pub threshold: u8,
+}
+
+#[event]
+pub struct ArbiterPanelApprovalUpdated {
+    pub arbiter_panel: Pubkey,
+    pub approved: bool,
+    pub admin: Pubkey,
 }
 
 #[event]
 pub struct EscrowInitialized {
     pub apartment_id: String,
     pub apartment_owner: Pubkey,
+    pub decision_deadline: i64,
+    pub required_stake: u64,
+    pub referral_reward: u64,
+    pub referral_limit: u32,
+    pub arbiter_panel: Pubkey,
+    pub dispute_window: i64,
+    pub slash_split: SlashSplit,
+    pub stake_mint: Option<Pubkey>,
+    pub stake_discount: StakeDiscount,
 }
 
 // ============================================================================
 // ERRORS
 // ============================================================================
 
+// Error codes follow declaration order, new variants are only appended at the end
 #[error_code]
 pub enum EscrowError {
     #[msg("Invalid amount: must be greater than 0")]
@@ -519,8 +4512,9 @@ pub enum EscrowError {
     StakeNotActive,
     #[msg("Unauthorized: only the lessor can perform this action")]
     UnauthorizedLessor,
-    #[msg("Invalid penalty wallet: must match the fixed penalty wallet")]
+    #[msg("Invalid penalty wallet: must match the protocol config")]
     InvalidPenaltyWallet,
+    // Deprecated, escrow phases are checked with InvalidEscrowStatus
     #[msg("Escrow is not active")]
     EscrowNotActive,
     #[msg("Invalid apartment ID")]
@@ -529,14 +4523,208 @@ pub enum EscrowError {
     InvalidTenant,
     #[msg("Escrow still has active stakes, cannot close")]
     EscrowNotEmpty,
+    // Deprecated, escrow accounts are checked by their seeds
     #[msg("Escrow not initialized for this apartment")]
     EscrowNotInitialized,
     #[msg("Insufficient funds to perform the operation")]
     InsufficientFunds,
     #[msg("Arithmetic overflow")]
     ArithmeticOverflow,
+    // Deprecated, the penalty wallet never signs and is checked with InvalidPenaltyWallet
     #[msg("Unauthorized: only the penalty wallet can perform this action")]
     UnauthorizedPenaltyWallet,
     #[msg("Missing referrer account")]
     MissingReferrerAccount,
-}
\ No newline at end of file
+    #[msg("Unauthorized: only the original staker can perform this action")]
+    UnauthorizedStaker,
+    #[msg("Invalid decision window: must be greater than 0 seconds")]
+    InvalidDecisionWindow,
+    #[msg("Decision deadline has not passed yet")]
+    DecisionWindowOpen,
+    #[msg("Invalid basis points: must be at most 10000")]
+    InvalidBasisPoints,
+    #[msg("Invalid slash amount: must be greater than 0 and at most the stake")]
+    InvalidSlashAmount,
+    #[msg("Invalid rent payer: must match the account that initialized the escrow")]
+    InvalidRentPayer,
+    #[msg("Stake is still active, cannot close")]
+    StakeStillActive,
+    #[msg("Invalid new lessor: must differ from the current lessor")]
+    InvalidNewLessor,
+    #[msg("No lessor transfer is pending")]
+    NoPendingLessorTransfer,
+    #[msg("Unauthorized: only the proposed lessor can accept the transfer")]
+    UnauthorizedPendingLessor,
+    #[msg("Invalid stake terms: referral reward cannot exceed the fully discounted required stake")]
+    InvalidStakeTerms,
+    #[msg("Stake is below the minimum required for this apartment")]
+    StakeBelowMinimum,
+    #[msg("Invalid referral account")]
+    InvalidReferral,
+    #[msg("Referral must be registered before the tenant stakes")]
+    ReferralAfterStake,
+    #[msg("Referral limit reached for this apartment")]
+    ReferralLimitReached,
+    #[msg("Missing referrer counter account")]
+    MissingReferrerCounter,
+    #[msg("Referrer counter does not match the referrer or apartment")]
+    InvalidReferrerCounter,
+    #[msg("A tenant has already been approved for this apartment")]
+    TenantAlreadyApproved,
+    #[msg("Only the approved tenant's stake can be slashed")]
+    NotApprovedTenant,
+    #[msg("Approved tenant cannot withdraw their stake")]
+    ApprovedTenantCannotWithdraw,
+    #[msg("Remaining accounts must be [stake_record, staker] pairs")]
+    InvalidRemainingAccounts,
+    #[msg("Invalid dispute window: must be greater than 0 seconds")]
+    InvalidDisputeWindow,
+    #[msg("Dispute is not in the expected state")]
+    InvalidDisputeStatus,
+    #[msg("Dispute window has closed")]
+    DisputeWindowClosed,
+    #[msg("Dispute window is still open")]
+    DisputeWindowOpen,
+    #[msg("Unauthorized: only an arbiter on the panel can perform this action")]
+    UnauthorizedArbiter,
+    #[msg("Invalid arbiter panel")]
+    InvalidArbiterPanel,
+    #[msg("Arbiter has already voted for this split")]
+    AlreadyVoted,
+    #[msg("Unauthorized: only the config admin can perform this action")]
+    UnauthorizedAdmin,
+    #[msg("Invalid slash split: shares must total 10000 basis points")]
+    InvalidSlashSplit,
+    #[msg("Resolution fee exceeds the protocol maximum")]
+    ResolutionFeeTooHigh,
+    #[msg("Program is paused")]
+    ProgramPaused,
+    #[msg("Account cannot be migrated")]
+    InvalidMigrationAccount,
+    #[msg("Account is already on the current layout version")]
+    AccountAlreadyMigrated,
+    #[msg("Active stake records do not add up to the escrow's total staked")]
+    IncompleteStakeRecords,
+    #[msg("Missing token account for a token escrow")]
+    MissingTokenAccount,
+    #[msg("Mint does not match the escrow's stake mint")]
+    InvalidStakeMint,
+    #[msg("Stake vault must be the escrow's associated token account")]
+    InvalidStakeVault,
+    #[msg("Missing account to receive a payout")]
+    MissingPayoutAccount,
+    #[msg("Payout account does not belong to the expected recipient")]
+    InvalidPayoutAccount,
+    #[msg("Stake mints that charge transfer fees are not supported")]
+    TransferFeeNotSupported,
+    #[msg("Instruction is not allowed in the escrow's current phase")]
+    InvalidEscrowStatus,
+    #[msg("Stake has not been converted to a security deposit")]
+    StakeNotDeposit,
+    #[msg("Too many itemized deductions")]
+    TooManyDeductions,
+    #[msg("Deductions exceed the security deposit")]
+    DeductionsExceedDeposit,
+    #[msg("Tenant response window is still open")]
+    ResponseWindowOpen,
+    #[msg("Evidence URI is too long")]
+    EvidenceUriTooLong,
+    #[msg("Line items do not add up to the itemized amount")]
+    LineItemsMismatch,
+    #[msg("Release split exceeds the stake amount")]
+    InvalidReleaseSplit,
+    #[msg("Stake record is not the PDA for this apartment and profile")]
+    InvalidStakeRecord,
+    #[msg("Arbiter panel has not been approved by the config admin")]
+    UnapprovedArbiterPanel,
+    #[msg("Legacy escrows need their apartment hash, stake records and terms to migrate")]
+    MissingLegacyEscrowTerms,
+    #[msg("A move-out settlement is pending for this deposit")]
+    MoveOutPending,
+    #[msg("Arbiter panel can still rule on this dispute")]
+    RulingWindowOpen,
+    #[msg("Ruling deadline for this dispute has passed")]
+    RulingWindowClosed,
+    #[msg("Lease end must be in the future")]
+    InvalidLeaseEnd,
+    #[msg("Lessor can still propose a move-out settlement for this deposit")]
+    MoveOutWindowOpen,
+    #[msg("Move-out deadline for this deposit has passed")]
+    MoveOutWindowClosed,
+    #[msg("Stake record uses an old layout, migrate it first")]
+    StakeRecordNotMigrated,
+    #[msg("Reputation account is required to record a completed lease")]
+    MissingReputation,
+    #[msg("Referral needs the tenant's consent signature in the preceding ed25519 instruction")]
+    MissingReferralConsent,
+    #[msg("Referral consent was not signed by the tenant for this referral")]
+    InvalidReferralConsent,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apply_bps_rounds_down_and_rejects_more_than_everything() {
+        assert_eq!(apply_bps(1_000_000, 100).unwrap(), 10_000);
+        assert_eq!(apply_bps(999, 100).unwrap(), 9);
+        assert_eq!(apply_bps(u64::MAX, 10_000).unwrap(), u64::MAX);
+        assert_eq!(apply_bps(1_000, 10_001).unwrap_err(), EscrowError::InvalidBasisPoints.into());
+    }
+
+    #[test]
+    fn split_penalty_gives_rounding_and_an_unpaid_referrer_share_to_the_penalty_wallet() {
+        let slash_split = SlashSplit { lessor_bps: 6_000, penalty_wallet_bps: 3_000, referrer_bps: 1_000 };
+
+        let shares = split_penalty(1_001, &slash_split, true).unwrap();
+        assert_eq!((shares.lessor, shares.penalty_wallet, shares.referrer), (600, 301, 100));
+
+        let shares = split_penalty(1_001, &slash_split, false).unwrap();
+        assert_eq!((shares.lessor, shares.penalty_wallet, shares.referrer), (600, 401, 0));
+    }
+
+    #[test]
+    fn total_deductions_adds_up_valid_line_items_only() {
+        let deduction = |amount, uri: &str| DeductionItem {
+            category: DeductionCategory::Damage,
+            amount,
+            evidence_hash: [7; 32],
+            uri: uri.to_string(),
+        };
+
+        assert_eq!(total_deductions(&[]).unwrap(), 0);
+        assert_eq!(total_deductions(&[deduction(250, "ipfs://photo"), deduction(750, "")]).unwrap(), 1_000);
+        assert_eq!(total_deductions(&[deduction(0, "")]).unwrap_err(), EscrowError::InvalidAmount.into());
+        assert_eq!(
+            total_deductions(&[deduction(1, &"x".repeat(MAX_EVIDENCE_URI_LEN + 1))]).unwrap_err(),
+            EscrowError::EvidenceUriTooLong.into()
+        );
+        assert_eq!(
+            total_deductions(&[deduction(u64::MAX, ""), deduction(1, "")]).unwrap_err(),
+            EscrowError::ArithmeticOverflow.into()
+        );
+        assert_eq!(
+            total_deductions(&vec![deduction(1, ""); MAX_DEDUCTIONS + 1]).unwrap_err(),
+            EscrowError::TooManyDeductions.into()
+        );
+    }
+
+    #[test]
+    fn slash_split_must_cover_the_whole_slash() {
+        let slash_split = |lessor_bps, penalty_wallet_bps, referrer_bps| {
+            SlashSplit { lessor_bps, penalty_wallet_bps, referrer_bps }
+        };
+
+        assert!(require_valid_slash_split(&slash_split(6_000, 3_000, 1_000)).is_ok());
+        assert!(require_valid_slash_split(&slash_split(0, 10_000, 0)).is_ok());
+        assert_eq!(
+            require_valid_slash_split(&slash_split(6_000, 3_000, 0)).unwrap_err(),
+            EscrowError::InvalidSlashSplit.into()
+        );
+        assert_eq!(
+            require_valid_slash_split(&slash_split(u16::MAX, 1, 0)).unwrap_err(),
+            EscrowError::InvalidSlashSplit.into()
+        );
+    }
+}
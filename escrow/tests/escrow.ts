@@ -0,0 +1,1850 @@
+import { BN, Program } from "@coral-xyz/anchor";
+import { BankrunProvider } from "anchor-bankrun";
+import { AddedAccount, Clock, ProgramTestContext, start } from "solana-bankrun";
+import {
+  ComputeBudgetProgram,
+  Ed25519Program,
+  Keypair,
+  LAMPORTS_PER_SOL,
+  PublicKey,
+  SystemProgram,
+  Transaction,
+  TransactionInstruction,
+} from "@solana/web3.js";
+import { createHash } from "crypto";
+import { readFileSync } from "fs";
+import * as path from "path";
+import { expect } from "chai";
+import { Escrow } from "../target/types/escrow";
+
+// Runs in-process on bankrun against the program from `anchor build`, so deadlines can be warped
+// and legacy accounts written directly: `anchor build && yarn test`
+
+const IDL = JSON.parse(readFileSync(path.join(__dirname, "../target/idl/escrow.json"), "utf8"));
+const PROGRAM_ID = new PublicKey(IDL.address);
+const BPF_LOADER_UPGRADEABLE = new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111");
+
+const REQUIRED_STAKE = LAMPORTS_PER_SOL;
+const RESOLUTION_FEE_BPS = 100;
+const ONE_DAY = 24 * 60 * 60;
+const LEGACY_DISPUTE_WINDOW = 3 * ONE_DAY;
+
+// ============================================================================
+// HELPERS
+// ============================================================================
+
+const hash = (input: string): number[] => Array.from(createHash("sha256").update(input).digest());
+
+const uniqueId = (prefix: string) => `${prefix}-${Math.random().toString(36).slice(2, 10)}`;
+
+const pda = (...seeds: (Buffer | number[])[]) =>
+  PublicKey.findProgramAddressSync(seeds.map((seed) => Buffer.from(seed)), PROGRAM_ID);
+
+const configPda = pda(Buffer.from("config"))[0];
+const treasuryPda = pda(Buffer.from("treasury"))[0];
+const escrowPda = (apartmentHash: number[]) => pda(Buffer.from("escrow"), apartmentHash);
+const stakePda = (apartmentHash: number[], profileHash: number[]) =>
+  pda(Buffer.from("stake"), apartmentHash, profileHash);
+const disputePda = (apartmentHash: number[], profileHash: number[]) =>
+  pda(Buffer.from("dispute"), apartmentHash, profileHash)[0];
+const referralPda = (apartmentHash: number[], profileHash: number[]) =>
+  pda(Buffer.from("referral"), apartmentHash, profileHash)[0];
+const referrerCounterPda = (apartmentHash: number[], referrer: PublicKey) =>
+  pda(Buffer.from("referrer_count"), apartmentHash, referrer.toBuffer())[0];
+const moveOutPda = (apartmentHash: number[], profileHash: number[]) =>
+  pda(Buffer.from("move_out"), apartmentHash, profileHash)[0];
+const reputationPda = (profileHash: number[], staker: PublicKey) =>
+  pda(Buffer.from("reputation"), profileHash, staker.toBuffer())[0];
+const arbiterPanelPda = (authority: PublicKey) => pda(Buffer.from("arbiter_panel"), authority.toBuffer())[0];
+
+// Program and ProgramData accounts of an upgradeable deploy, initialize_config only accepts the upgrade authority
+const upgradeableProgram = (upgradeAuthority: PublicKey): AddedAccount[] => {
+  const [programData] = PublicKey.findProgramAddressSync([PROGRAM_ID.toBuffer()], BPF_LOADER_UPGRADEABLE);
+  const elf = readFileSync(path.join(__dirname, "../target/deploy/escrow.so"));
+
+  const programAccount = Buffer.alloc(36);
+  programAccount.writeUInt32LE(2, 0);
+  programData.toBuffer().copy(programAccount, 4);
+
+  // UpgradeableLoaderState::ProgramData { slot: 0, upgrade_authority_address: Some(..) }
+  const programDataHeader = Buffer.alloc(45);
+  programDataHeader.writeUInt32LE(3, 0);
+  programDataHeader.writeUInt8(1, 12);
+  upgradeAuthority.toBuffer().copy(programDataHeader, 13);
+
+  return [
+    {
+      address: PROGRAM_ID,
+      info: { lamports: LAMPORTS_PER_SOL, data: programAccount, owner: BPF_LOADER_UPGRADEABLE, executable: true },
+    },
+    {
+      address: programData,
+      info: {
+        lamports: 100 * LAMPORTS_PER_SOL,
+        data: Buffer.concat([programDataHeader, elf]),
+        owner: BPF_LOADER_UPGRADEABLE,
+        executable: false,
+      },
+    },
+  ];
+};
+
+const fundedAccount = (wallet: Keypair): AddedAccount => ({
+  address: wallet.publicKey,
+  info: { lamports: 100 * LAMPORTS_PER_SOL, data: Buffer.alloc(0), owner: SystemProgram.programId, executable: false },
+});
+
+// Account data as written by the program before layout versioning (version 0)
+const discriminator = (account: string) => createHash("sha256").update(`account:${account}`).digest().subarray(0, 8);
+
+const borshString = (value: string) => {
+  const buffer = Buffer.alloc(4 + value.length);
+  buffer.writeUInt32LE(value.length, 0);
+  buffer.write(value, 4);
+  return buffer;
+};
+
+const u64 = (value: number) => {
+  const buffer = Buffer.alloc(8);
+  buffer.writeBigUInt64LE(BigInt(value));
+  return buffer;
+};
+
+const padTo = (data: Buffer, length: number) => Buffer.concat([data, Buffer.alloc(length - data.length)]);
+
+// ApartmentEscrow { apartment_id, lessor, total_staked, is_active, bump }
+const legacyEscrowData = (apartmentId: string, lessor: PublicKey, totalStaked: number, bump: number) =>
+  padTo(
+    Buffer.concat([
+      discriminator("ApartmentEscrow"),
+      borshString(apartmentId),
+      lessor.toBuffer(),
+      u64(totalStaked),
+      Buffer.from([1, bump]),
+    ]),
+    8 + 4 + 50 + 32 + 8 + 1 + 1
+  );
+
+// StakeRecord { tenant_profile_id, apartment_id, staker, amount, is_active, bump }
+const legacyStakeRecordData = (
+  profileId: string,
+  apartmentId: string,
+  staker: PublicKey,
+  amount: number,
+  bump: number
+) =>
+  padTo(
+    Buffer.concat([
+      discriminator("StakeRecord"),
+      borshString(profileId),
+      borshString(apartmentId),
+      staker.toBuffer(),
+      u64(amount),
+      Buffer.from([1, bump]),
+    ]),
+    8 + 4 + 50 + 4 + 50 + 32 + 8 + 1 + 1
+  );
+
+interface Terms {
+  decisionWindow: number;
+  requiredStake: number;
+  referralReward: number;
+  referralLimit: number;
+  disputeWindow: number;
+  slashSplit: { lessorBps: number; penaltyWalletBps: number; referrerBps: number };
+  stakeDiscount: { bpsPerCompletedStake: number; maxDiscountBps: number };
+}
+
+const DEFAULT_TERMS: Terms = {
+  decisionWindow: 7 * ONE_DAY,
+  requiredStake: REQUIRED_STAKE,
+  referralReward: 0,
+  referralLimit: 5,
+  disputeWindow: 3 * ONE_DAY,
+  slashSplit: { lessorBps: 6000, penaltyWalletBps: 3000, referrerBps: 1000 },
+  stakeDiscount: { bpsPerCompletedStake: 0, maxDiscountBps: 0 },
+};
+
+interface Listing {
+  apartmentId: string;
+  apartmentHash: number[];
+  escrow: PublicKey;
+  lessor: Keypair;
+}
+
+interface Applicant {
+  profileId: string;
+  profileHash: number[];
+  wallet: Keypair;
+  stakeRecord: PublicKey;
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+describe("escrow", () => {
+  const admin = Keypair.generate();
+  const penaltyWallet = Keypair.generate();
+  const panelAuthority = Keypair.generate();
+  const arbiters = [Keypair.generate(), Keypair.generate(), Keypair.generate()];
+
+  let context: ProgramTestContext;
+  let program: Program<Escrow>;
+  let arbiterPanel: PublicKey;
+
+  // Bankrun keeps one blockhash, a compute limit unique to each transaction keeps repeats from being deduplicated
+  let transactionCount = 0;
+
+  // Send instructions paid for by the bankrun payer, so balances of the other wallets only move by payouts
+  const send = async (instructions: TransactionInstruction[], signers: Keypair[] = []) => {
+    transactionCount += 1;
+    const transaction = new Transaction().add(
+      ComputeBudgetProgram.setComputeUnitLimit({ units: 1_400_000 - transactionCount }),
+      ...instructions
+    );
+    transaction.recentBlockhash = context.lastBlockhash;
+    transaction.feePayer = context.payer.publicKey;
+    transaction.sign(context.payer, ...signers);
+    return context.banksClient.tryProcessTransaction(transaction);
+  };
+
+  const expectOk = async (instructions: TransactionInstruction[], signers: Keypair[] = []) => {
+    const { result, meta } = await send(instructions, signers);
+    expect(result, meta?.logMessages.join("\n")).to.equal(null);
+  };
+
+  const expectError = async (instructions: TransactionInstruction[], signers: Keypair[], name: string) => {
+    const { result } = await send(instructions, signers);
+    const code = IDL.errors.find((error: { name: string }) => error.name === name).code;
+    expect(result, `expected ${name}`).to.include(`custom program error: 0x${code.toString(16)}`);
+  };
+
+  const balance = async (key: PublicKey) => Number(await context.banksClient.getBalance(key));
+
+  const lamportsOf = async (key: PublicKey) => (await context.banksClient.getAccount(key))?.lamports ?? 0;
+
+  const now = async () => Number((await context.banksClient.getClock()).unixTimestamp);
+
+  const warpTo = async (timestamp: number) => {
+    const clock = await context.banksClient.getClock();
+    context.setClock(
+      new Clock(clock.slot, clock.epochStartTimestamp, clock.epoch, clock.leaderScheduleEpoch, BigInt(timestamp))
+    );
+  };
+
+  const fundedWallet = () => {
+    const wallet = Keypair.generate();
+    const { info } = fundedAccount(wallet);
+    context.setAccount(wallet.publicKey, info);
+    return wallet;
+  };
+
+  const createListing = async (overrides: Partial<Terms> = {}): Promise<Listing> => {
+    const terms = { ...DEFAULT_TERMS, ...overrides };
+    const lessor = fundedWallet();
+    const apartmentId = uniqueId("apt");
+    const apartmentHash = hash(apartmentId);
+    const [escrow] = escrowPda(apartmentHash);
+
+    await expectOk(
+      [
+        await program.methods
+          .initializeApartment(
+            apartmentHash,
+            apartmentId,
+            lessor.publicKey,
+            new BN(terms.decisionWindow),
+            new BN(terms.requiredStake),
+            new BN(terms.referralReward),
+            terms.referralLimit,
+            new BN(terms.disputeWindow),
+            terms.slashSplit,
+            terms.stakeDiscount
+          )
+          .accountsPartial({
+            escrowAccount: escrow,
+            config: configPda,
+            arbiterPanel,
+            initializer: lessor.publicKey,
+            stakeMint: null,
+            stakeVault: null,
+            tokenProgram: null,
+            associatedTokenProgram: null,
+          })
+          .instruction(),
+      ],
+      [lessor]
+    );
+
+    return { apartmentId, apartmentHash, escrow, lessor };
+  };
+
+  const newApplicant = (listing: Listing): Applicant => {
+    const profileId = uniqueId("profile");
+    const profileHash = hash(profileId);
+    return {
+      profileId,
+      profileHash,
+      wallet: fundedWallet(),
+      stakeRecord: stakePda(listing.apartmentHash, profileHash)[0],
+    };
+  };
+
+  const stakeIx = (
+    listing: Listing,
+    applicant: Applicant,
+    amount: number = REQUIRED_STAKE,
+    reputation: PublicKey | null = null
+  ) =>
+    program.methods
+      .stakeForApartment(listing.apartmentHash, new BN(amount), applicant.profileHash, listing.apartmentId, applicant.profileId)
+      .accountsPartial({
+        escrowAccount: listing.escrow,
+        config: configPda,
+        stakeRecord: applicant.stakeRecord,
+        reputation,
+        staker: applicant.wallet.publicKey,
+        stakeMint: null,
+        stakeVault: null,
+        stakerTokenAccount: null,
+        tokenProgram: null,
+      })
+      .instruction();
+
+  const stakedApplicant = async (listing: Listing) => {
+    const applicant = newApplicant(listing);
+    await expectOk([await stakeIx(listing, applicant)], [applicant.wallet]);
+    return applicant;
+  };
+
+  const approveIx = (listing: Listing, applicant: Applicant) =>
+    program.methods
+      .approveTenant(listing.apartmentHash, applicant.profileHash, listing.apartmentId, applicant.profileId)
+      .accountsPartial({ escrowAccount: listing.escrow, stakeRecord: applicant.stakeRecord, lessor: listing.lessor.publicKey })
+      .instruction();
+
+  const startLeaseIx = (listing: Listing) =>
+    program.methods
+      .startLease(listing.apartmentHash, listing.apartmentId)
+      .accountsPartial({ escrowAccount: listing.escrow, lessor: listing.lessor.publicKey })
+      .instruction();
+
+  // An approved tenant whose lease has started
+  const leasedTenant = async (listing: Listing) => {
+    const tenant = await stakedApplicant(listing);
+    await expectOk([await approveIx(listing, tenant), await startLeaseIx(listing)], [listing.lessor]);
+    return tenant;
+  };
+
+  const withdrawIx = (listing: Listing, applicant: Applicant) =>
+    program.methods
+      .withdrawStake(listing.apartmentHash, applicant.profileHash, listing.apartmentId, applicant.profileId)
+      .accountsPartial({
+        escrowAccount: listing.escrow,
+        stakeRecord: applicant.stakeRecord,
+        staker: applicant.wallet.publicKey,
+        stakeMint: null,
+        stakeVault: null,
+        stakerTokenAccount: null,
+        tokenProgram: null,
+      })
+      .instruction();
+
+  const refundExpiredIx = (listing: Listing, applicant: Applicant, caller: Keypair) =>
+    program.methods
+      .refundExpiredStake(listing.apartmentHash, applicant.profileHash, listing.apartmentId, applicant.profileId)
+      .accountsPartial({
+        escrowAccount: listing.escrow,
+        stakeRecord: applicant.stakeRecord,
+        staker: applicant.wallet.publicKey,
+        caller: caller.publicKey,
+        stakeMint: null,
+        stakeVault: null,
+        stakerTokenAccount: null,
+        tokenProgram: null,
+      })
+      .instruction();
+
+  const slashIx = (
+    listing: Listing,
+    tenant: Applicant,
+    slashAmount: { basisPoints: [number] } | { amount: [BN] },
+    lineItems: object[] = []
+  ) =>
+    program.methods
+      .slashStake(
+        listing.apartmentHash,
+        tenant.profileHash,
+        listing.apartmentId,
+        tenant.profileId,
+        listing.lessor.publicKey,
+        slashAmount,
+        { propertyDamage: {} },
+        lineItems as any
+      )
+      .accountsPartial({
+        escrowAccount: listing.escrow,
+        config: configPda,
+        stakeRecord: tenant.stakeRecord,
+        slashDispute: disputePda(listing.apartmentHash, tenant.profileHash),
+        reputation: reputationPda(tenant.profileHash, tenant.wallet.publicKey),
+        lessor: listing.lessor.publicKey,
+        staker: tenant.wallet.publicKey,
+        referral: referralPda(listing.apartmentHash, tenant.profileHash),
+        stakeMint: null,
+        stakeVault: null,
+        stakerTokenAccount: null,
+        tokenProgram: null,
+      })
+      .instruction();
+
+  const resolveIx = (listing: Listing, applicant: Applicant, approved: boolean, referrer: PublicKey | null = null) =>
+    program.methods
+      .resolveStake(
+        listing.apartmentHash,
+        applicant.profileHash,
+        listing.apartmentId,
+        applicant.profileId,
+        listing.lessor.publicKey,
+        []
+      )
+      .accountsPartial({
+        escrowAccount: listing.escrow,
+        stakeRecord: applicant.stakeRecord,
+        reputation: approved ? reputationPda(applicant.profileHash, applicant.wallet.publicKey) : null,
+        lessor: listing.lessor.publicKey,
+        staker: applicant.wallet.publicKey,
+        referral: referralPda(listing.apartmentHash, applicant.profileHash),
+        referrer,
+        referrerCounter: referrer ? referrerCounterPda(listing.apartmentHash, referrer) : null,
+        config: configPda,
+        treasury: treasuryPda,
+        stakeMint: null,
+        stakeVault: null,
+        stakerTokenAccount: null,
+        referrerTokenAccount: null,
+        treasuryTokenAccount: null,
+        tokenProgram: null,
+      })
+      .instruction();
+
+  const mutualReleaseIx = (
+    listing: Listing,
+    applicant: Applicant,
+    lessorAmount: number,
+    referrerAmount: number = 0,
+    referrer: PublicKey | null = null
+  ) =>
+    program.methods
+      .mutualRelease(listing.apartmentHash, applicant.profileHash, new BN(lessorAmount), new BN(referrerAmount))
+      .accountsPartial({
+        escrowAccount: listing.escrow,
+        stakeRecord: applicant.stakeRecord,
+        lessor: listing.lessor.publicKey,
+        staker: applicant.wallet.publicKey,
+        referral: referralPda(listing.apartmentHash, applicant.profileHash),
+        referrer,
+        referrerCounter: referrer ? referrerCounterPda(listing.apartmentHash, referrer) : null,
+        config: configPda,
+        stakeMint: null,
+        stakeVault: null,
+        stakerTokenAccount: null,
+        lessorTokenAccount: null,
+        referrerTokenAccount: null,
+        tokenProgram: null,
+      })
+      .instruction();
+
+  // remaining_accounts are [stake_record, staker] pairs in the order of the profile hashes
+  const refundApplicantsIx = (listing: Listing, batch: Applicant[]) =>
+    program.methods
+      .refundApplicants(
+        listing.apartmentHash,
+        listing.apartmentId,
+        batch.map((applicant) => applicant.profileHash)
+      )
+      .accountsPartial({
+        escrowAccount: listing.escrow,
+        config: configPda,
+        lessor: listing.lessor.publicKey,
+        stakeMint: null,
+        stakeVault: null,
+        tokenProgram: null,
+      })
+      .remainingAccounts(
+        batch.flatMap((applicant) => [
+          { pubkey: applicant.stakeRecord, isSigner: false, isWritable: true },
+          { pubkey: applicant.wallet.publicKey, isSigner: false, isWritable: true },
+        ])
+      )
+      .instruction();
+
+  const closeEscrowIx = (listing: Listing) =>
+    program.methods
+      .closeEscrow(listing.apartmentHash, listing.apartmentId)
+      .accountsPartial({
+        escrowAccount: listing.escrow,
+        lessor: listing.lessor.publicKey,
+        rentPayer: listing.lessor.publicKey,
+        stakeMint: null,
+        stakeVault: null,
+        lessorTokenAccount: null,
+        tokenProgram: null,
+      })
+      .instruction();
+
+  const closeStakeRecordIx = (listing: Listing, applicant: Applicant) =>
+    program.methods
+      .closeStakeRecord(listing.apartmentHash, applicant.profileHash, listing.apartmentId, applicant.profileId)
+      .accountsPartial({
+        stakeRecord: applicant.stakeRecord,
+        escrowAccount: listing.escrow,
+        staker: applicant.wallet.publicKey,
+      })
+      .instruction();
+
+  const disputeIx = (listing: Listing, tenant: Applicant) =>
+    program.methods
+      .disputeSlash(listing.apartmentHash, tenant.profileHash)
+      .accountsPartial({
+        slashDispute: disputePda(listing.apartmentHash, tenant.profileHash),
+        staker: tenant.wallet.publicKey,
+      })
+      .instruction();
+
+  const voteIx = (listing: Listing, tenant: Applicant, arbiter: Keypair, tenantShareBps: number) =>
+    program.methods
+      .voteOnDispute(listing.apartmentHash, tenant.profileHash, tenantShareBps)
+      .accountsPartial({
+        escrowAccount: listing.escrow,
+        slashDispute: disputePda(listing.apartmentHash, tenant.profileHash),
+        reputation: reputationPda(tenant.profileHash, tenant.wallet.publicKey),
+        arbiterPanel,
+        arbiter: arbiter.publicKey,
+        staker: tenant.wallet.publicKey,
+        config: configPda,
+        penaltyWallet: penaltyWallet.publicKey,
+        lessor: listing.lessor.publicKey,
+        referral: referralPda(listing.apartmentHash, tenant.profileHash),
+        referrer: null,
+        rentPayer: listing.lessor.publicKey,
+        stakeMint: null,
+        stakeVault: null,
+        stakerTokenAccount: null,
+        lessorTokenAccount: null,
+        penaltyWalletTokenAccount: null,
+        referrerTokenAccount: null,
+        tokenProgram: null,
+      })
+      .instruction();
+
+  const finalizeIx = (listing: Listing, tenant: Applicant, caller: Keypair, referrer: PublicKey | null = null) =>
+    program.methods
+      .finalizeSlash(listing.apartmentHash, tenant.profileHash)
+      .accountsPartial({
+        escrowAccount: listing.escrow,
+        slashDispute: disputePda(listing.apartmentHash, tenant.profileHash),
+        reputation: reputationPda(tenant.profileHash, tenant.wallet.publicKey),
+        config: configPda,
+        penaltyWallet: penaltyWallet.publicKey,
+        lessor: listing.lessor.publicKey,
+        referral: referralPda(listing.apartmentHash, tenant.profileHash),
+        referrer,
+        rentPayer: listing.lessor.publicKey,
+        caller: caller.publicKey,
+        stakeMint: null,
+        stakeVault: null,
+        lessorTokenAccount: null,
+        penaltyWalletTokenAccount: null,
+        referrerTokenAccount: null,
+        tokenProgram: null,
+      })
+      .instruction();
+
+  // The tenant's consent to the referral, signed by `signer`, followed by the referral registration
+  const registerReferralIxs = async (listing: Listing, applicant: Applicant, referrer: Keypair, signer: Keypair) => {
+    const message = Buffer.from(
+      `Referral consent: ${referrer.publicKey.toBase58()} referred ${applicant.profileId} for apartment ${listing.apartmentId}`
+    );
+    return [
+      Ed25519Program.createInstructionWithPrivateKey({ privateKey: signer.secretKey, message }),
+      await program.methods
+        .registerReferral(listing.apartmentHash, applicant.profileHash, listing.apartmentId, applicant.profileId)
+        .accountsPartial({
+          escrowAccount: listing.escrow,
+          referral: referralPda(listing.apartmentHash, applicant.profileHash),
+          referrerCounter: referrerCounterPda(listing.apartmentHash, referrer.publicKey),
+          stakeRecord: applicant.stakeRecord,
+          referrer: referrer.publicKey,
+          tenant: applicant.wallet.publicKey,
+        })
+        .instruction(),
+    ];
+  };
+
+  before(async () => {
+    context = await start(
+      [],
+      [
+        ...upgradeableProgram(admin.publicKey),
+        fundedAccount(admin),
+        fundedAccount(panelAuthority),
+        ...arbiters.map(fundedAccount),
+      ]
+    );
+    // Programs deployed at genesis become visible from the next slot
+    context.warpToSlot(BigInt(2));
+
+    program = new Program<Escrow>(IDL, new BankrunProvider(context));
+
+    const [programData] = PublicKey.findProgramAddressSync([PROGRAM_ID.toBuffer()], BPF_LOADER_UPGRADEABLE);
+    await expectOk(
+      [
+        await program.methods
+          .initializeConfig(penaltyWallet.publicKey, RESOLUTION_FEE_BPS)
+          .accountsPartial({
+            config: configPda,
+            treasury: treasuryPda,
+            program: PROGRAM_ID,
+            programData,
+            admin: admin.publicKey,
+          })
+          .instruction(),
+      ],
+      [admin]
+    );
+
+    arbiterPanel = arbiterPanelPda(panelAuthority.publicKey);
+    await expectOk(
+      [
+        await program.methods
+          .createArbiterPanel(
+            arbiters.map((arbiter) => arbiter.publicKey),
+            2
+          )
+          .accountsPartial({ arbiterPanel, authority: panelAuthority.publicKey })
+          .instruction(),
+      ],
+      [panelAuthority]
+    );
+    await expectOk(
+      [
+        await program.methods
+          .setArbiterPanelApproval(true)
+          .accountsPartial({ config: configPda, arbiterPanel, admin: admin.publicKey })
+          .instruction(),
+      ],
+      [admin]
+    );
+  });
+
+  describe("withdraw_stake", () => {
+    it("returns the whole stake to its staker and settles the record", async () => {
+      const listing = await createListing();
+      const applicant = await stakedApplicant(listing);
+
+      const before = await balance(applicant.wallet.publicKey);
+      await expectOk([await withdrawIx(listing, applicant)], [applicant.wallet]);
+      expect(await balance(applicant.wallet.publicKey)).to.equal(before + REQUIRED_STAKE);
+
+      const stakeRecord = await program.account.stakeRecord.fetch(applicant.stakeRecord);
+      expect(stakeRecord.status).to.deep.equal({ settled: {} });
+      expect(stakeRecord.amount.toNumber()).to.equal(0);
+
+      const escrow = await program.account.apartmentEscrow.fetch(listing.escrow);
+      expect(escrow.activeStakes).to.equal(0);
+      expect(escrow.totalStaked.toNumber()).to.equal(0);
+
+      // Nothing left to withdraw a second time
+      await expectError([await withdrawIx(listing, applicant)], [applicant.wallet], "StakeNotActive");
+    });
+
+    it("only lets the original staker withdraw", async () => {
+      const listing = await createListing();
+      const applicant = await stakedApplicant(listing);
+      const outsider = fundedWallet();
+
+      await expectError(
+        [await withdrawIx(listing, { ...applicant, wallet: outsider })],
+        [outsider],
+        "UnauthorizedStaker"
+      );
+      expect((await program.account.stakeRecord.fetch(applicant.stakeRecord)).amount.toNumber()).to.equal(REQUIRED_STAKE);
+    });
+  });
+
+  describe("refund_expired_stake", () => {
+    it("refunds a stake to its staker once the lessor misses the decision deadline", async () => {
+      const listing = await createListing();
+      const applicant = await stakedApplicant(listing);
+      const caller = fundedWallet();
+
+      await expectError([await refundExpiredIx(listing, applicant, caller)], [caller], "DecisionWindowOpen");
+
+      const { decisionDeadline } = await program.account.apartmentEscrow.fetch(listing.escrow);
+      expect(decisionDeadline.toNumber()).to.equal((await now()) + DEFAULT_TERMS.decisionWindow);
+      await warpTo(decisionDeadline.toNumber() + 1);
+
+      const before = await balance(applicant.wallet.publicKey);
+      await expectOk([await refundExpiredIx(listing, applicant, caller)], [caller]);
+      expect(await balance(applicant.wallet.publicKey)).to.equal(before + REQUIRED_STAKE);
+
+      const stakeRecord = await program.account.stakeRecord.fetch(applicant.stakeRecord);
+      expect(stakeRecord.status).to.deep.equal({ settled: {} });
+      expect(stakeRecord.amount.toNumber()).to.equal(0);
+    });
+  });
+
+  describe("slash_stake", () => {
+    it("slashes part of a stake with a reason and refunds the rest", async () => {
+      const listing = await createListing();
+      const tenant = await stakedApplicant(listing);
+      await expectOk([await approveIx(listing, tenant)], [listing.lessor]);
+
+      await expectError([await slashIx(listing, tenant, { basisPoints: [10_001] })], [listing.lessor], "InvalidBasisPoints");
+      await expectError(
+        [await slashIx(listing, tenant, { amount: [new BN(REQUIRED_STAKE + 1)] })],
+        [listing.lessor],
+        "InvalidSlashAmount"
+      );
+
+      const before = await balance(tenant.wallet.publicKey);
+      await expectOk([await slashIx(listing, tenant, { basisPoints: [2500] })], [listing.lessor]);
+      expect(await balance(tenant.wallet.publicKey)).to.equal(before + REQUIRED_STAKE * 0.75);
+
+      const slashed = await program.account.slashDispute.fetch(disputePda(listing.apartmentHash, tenant.profileHash));
+      expect(slashed.amount.toNumber()).to.equal(REQUIRED_STAKE * 0.25);
+      expect(slashed.reason).to.deep.equal({ propertyDamage: {} });
+      expect((await program.account.stakeRecord.fetch(tenant.stakeRecord)).status).to.deep.equal({ settled: {} });
+    });
+  });
+
+  describe("close_escrow", () => {
+    it("only closes an empty escrow and returns its rent to the rent payer", async () => {
+      const listing = await createListing();
+      const applicant = await stakedApplicant(listing);
+
+      await expectError([await closeEscrowIx(listing)], [listing.lessor], "EscrowNotEmpty");
+
+      await expectOk([await withdrawIx(listing, applicant)], [applicant.wallet]);
+
+      const before = await balance(listing.lessor.publicKey);
+      const rent = await lamportsOf(listing.escrow);
+      await expectOk([await closeEscrowIx(listing)], [listing.lessor]);
+      expect(await balance(listing.lessor.publicKey)).to.equal(before + rent);
+      expect(await context.banksClient.getAccount(listing.escrow)).to.equal(null);
+    });
+  });
+
+  describe("close_stake_record", () => {
+    it("returns the rent of a settled stake record to its staker", async () => {
+      const listing = await createListing();
+      const applicant = await stakedApplicant(listing);
+
+      await expectError([await closeStakeRecordIx(listing, applicant)], [applicant.wallet], "StakeStillActive");
+
+      await expectOk([await withdrawIx(listing, applicant)], [applicant.wallet]);
+
+      const outsider = fundedWallet();
+      await expectError(
+        [await closeStakeRecordIx(listing, { ...applicant, wallet: outsider })],
+        [outsider],
+        "UnauthorizedStaker"
+      );
+
+      const before = await balance(applicant.wallet.publicKey);
+      const rent = await lamportsOf(applicant.stakeRecord);
+      await expectOk([await closeStakeRecordIx(listing, applicant)], [applicant.wallet]);
+      expect(await balance(applicant.wallet.publicKey)).to.equal(before + rent);
+      expect(await context.banksClient.getAccount(applicant.stakeRecord)).to.equal(null);
+    });
+  });
+
+  describe("lessor transfer", () => {
+    const proposeIx = (listing: Listing, lessor: Keypair, newLessor: PublicKey) =>
+      program.methods
+        .proposeLessorTransfer(listing.apartmentHash, listing.apartmentId, newLessor)
+        .accountsPartial({ escrowAccount: listing.escrow, lessor: lessor.publicKey })
+        .instruction();
+
+    const cancelIx = (listing: Listing) =>
+      program.methods
+        .cancelLessorTransfer(listing.apartmentHash, listing.apartmentId)
+        .accountsPartial({ escrowAccount: listing.escrow, lessor: listing.lessor.publicKey })
+        .instruction();
+
+    const acceptIx = (listing: Listing, newLessor: Keypair) =>
+      program.methods
+        .acceptLessorTransfer(listing.apartmentHash, listing.apartmentId)
+        .accountsPartial({ escrowAccount: listing.escrow, newLessor: newLessor.publicKey })
+        .instruction();
+
+    it("hands the escrow over only once the proposed lessor accepts", async () => {
+      const listing = await createListing();
+      const newLessor = fundedWallet();
+      const outsider = fundedWallet();
+
+      await expectError([await proposeIx(listing, outsider, outsider.publicKey)], [outsider], "UnauthorizedLessor");
+
+      // A cancelled proposal can no longer be accepted
+      await expectOk([await proposeIx(listing, listing.lessor, newLessor.publicKey)], [listing.lessor]);
+      await expectOk([await cancelIx(listing)], [listing.lessor]);
+      await expectError([await acceptIx(listing, newLessor)], [newLessor], "NoPendingLessorTransfer");
+
+      await expectOk([await proposeIx(listing, listing.lessor, newLessor.publicKey)], [listing.lessor]);
+      await expectError([await acceptIx(listing, outsider)], [outsider], "UnauthorizedPendingLessor");
+      await expectOk([await acceptIx(listing, newLessor)], [newLessor]);
+
+      const escrow = await program.account.apartmentEscrow.fetch(listing.escrow);
+      expect(escrow.lessor.equals(newLessor.publicKey)).to.equal(true);
+      expect(escrow.pendingLessor).to.equal(null);
+
+      // The previous lessor lost control of the escrow
+      await expectError([await proposeIx(listing, listing.lessor, outsider.publicKey)], [listing.lessor], "UnauthorizedLessor");
+    });
+  });
+
+  describe("stake terms", () => {
+    it("refuses stakes below the escrow's required stake", async () => {
+      const listing = await createListing();
+      const applicant = newApplicant(listing);
+
+      await expectError([await stakeIx(listing, applicant, REQUIRED_STAKE - 1)], [applicant.wallet], "StakeBelowMinimum");
+      await expectOk([await stakeIx(listing, applicant)], [applicant.wallet]);
+
+      const escrow = await program.account.apartmentEscrow.fetch(listing.escrow);
+      expect(escrow.requiredStake.toNumber()).to.equal(REQUIRED_STAKE);
+      expect(escrow.totalStaked.toNumber()).to.equal(REQUIRED_STAKE);
+    });
+
+    it("refuses a referral reward above the required stake", async () => {
+      const lessor = fundedWallet();
+      const apartmentId = uniqueId("apt");
+      const apartmentHash = hash(apartmentId);
+
+      await expectError(
+        [
+          await program.methods
+            .initializeApartment(
+              apartmentHash,
+              apartmentId,
+              lessor.publicKey,
+              new BN(DEFAULT_TERMS.decisionWindow),
+              new BN(REQUIRED_STAKE),
+              new BN(REQUIRED_STAKE + 1),
+              DEFAULT_TERMS.referralLimit,
+              new BN(DEFAULT_TERMS.disputeWindow),
+              DEFAULT_TERMS.slashSplit,
+              DEFAULT_TERMS.stakeDiscount
+            )
+            .accountsPartial({
+              escrowAccount: escrowPda(apartmentHash)[0],
+              config: configPda,
+              arbiterPanel,
+              initializer: lessor.publicKey,
+              stakeMint: null,
+              stakeVault: null,
+              tokenProgram: null,
+              associatedTokenProgram: null,
+            })
+            .instruction(),
+        ],
+        [lessor],
+        "InvalidStakeTerms"
+      );
+    });
+  });
+
+  describe("register_referral", () => {
+    it("registers a referral only with the tenant's consent and before they stake", async () => {
+      const listing = await createListing();
+      const referrer = fundedWallet();
+      const tenant = newApplicant(listing);
+
+      // A consent signed by anyone but the tenant is refused
+      await expectError(await registerReferralIxs(listing, tenant, referrer, referrer), [referrer], "InvalidReferralConsent");
+      // So is a registration without any consent
+      await expectError(
+        (await registerReferralIxs(listing, tenant, referrer, tenant.wallet)).slice(1),
+        [referrer],
+        "MissingReferralConsent"
+      );
+
+      await expectOk(await registerReferralIxs(listing, tenant, referrer, tenant.wallet), [referrer]);
+
+      const referral = await program.account.referral.fetch(referralPda(listing.apartmentHash, tenant.profileHash));
+      expect(referral.referrer.equals(referrer.publicKey)).to.equal(true);
+      expect(referral.tenant.equals(tenant.wallet.publicKey)).to.equal(true);
+
+      // Referrals cannot be added once the tenant has staked
+      const applicant = await stakedApplicant(listing);
+      await expectError(await registerReferralIxs(listing, applicant, referrer, applicant.wallet), [referrer], "ReferralAfterStake");
+    });
+  });
+
+  describe("referral limit", () => {
+    it("lets the approved tenant keep the referral reward once the referrer reached the limit", async () => {
+      const referralReward = REQUIRED_STAKE / 10;
+      const listing = await createListing({ referralLimit: 1, referralReward });
+      const referrer = fundedWallet();
+      const applicant = newApplicant(listing);
+      const tenant = newApplicant(listing);
+
+      for (const referred of [applicant, tenant]) {
+        await expectOk(await registerReferralIxs(listing, referred, referrer, referred.wallet), [referrer]);
+        await expectOk([await stakeIx(listing, referred)], [referred.wallet]);
+      }
+
+      // The other applicant's negotiated release pays the referrer and uses up the limit
+      await expectOk(
+        [await mutualReleaseIx(listing, applicant, 0, referralReward, referrer.publicKey)],
+        [listing.lessor, applicant.wallet]
+      );
+      const counter = await program.account.referrerCounter.fetch(referrerCounterPda(listing.apartmentHash, referrer.publicKey));
+      expect(counter.referralsPaid).to.equal(1);
+
+      const referrerBefore = await balance(referrer.publicKey);
+      const tenantBefore = await balance(tenant.wallet.publicKey);
+      await expectOk(
+        [await approveIx(listing, tenant), await resolveIx(listing, tenant, true, referrer.publicKey)],
+        [listing.lessor]
+      );
+
+      const fee = (REQUIRED_STAKE * RESOLUTION_FEE_BPS) / 10_000;
+      expect(await balance(referrer.publicKey)).to.equal(referrerBefore);
+      expect(await balance(tenant.wallet.publicKey)).to.equal(tenantBefore + REQUIRED_STAKE - fee);
+    });
+  });
+
+  describe("approve_tenant", () => {
+    it("lets other applicants withdraw once a tenant is approved, but not the approved tenant", async () => {
+      const listing = await createListing();
+      const tenant = await stakedApplicant(listing);
+      const applicant = await stakedApplicant(listing);
+      await expectOk([await approveIx(listing, tenant)], [listing.lessor]);
+
+      // Only the approved tenant can be slashed
+      await expectError([await slashIx(listing, applicant, { basisPoints: [10_000] })], [listing.lessor], "NotApprovedTenant");
+
+      const before = await balance(applicant.wallet.publicKey);
+      await expectOk([await withdrawIx(listing, applicant)], [applicant.wallet]);
+      expect(await balance(applicant.wallet.publicKey)).to.equal(before + REQUIRED_STAKE);
+
+      await expectError([await withdrawIx(listing, tenant)], [tenant.wallet], "ApprovedTenantCannotWithdraw");
+
+      const escrow = await program.account.apartmentEscrow.fetch(listing.escrow);
+      expect(escrow.approvedProfile).to.deep.equal(tenant.profileHash);
+      expect(escrow.activeStakes).to.equal(1);
+      expect(escrow.totalStaked.toNumber()).to.equal(REQUIRED_STAKE);
+    });
+  });
+
+  describe("refund_applicants", () => {
+    it("refunds every applicant but the approved tenant in one call", async () => {
+      const listing = await createListing();
+      const tenant = await stakedApplicant(listing);
+      const applicants = [await stakedApplicant(listing), await stakedApplicant(listing)];
+      const withdrawn = await stakedApplicant(listing);
+      await expectOk([await approveIx(listing, tenant)], [listing.lessor]);
+      await expectOk([await withdrawIx(listing, withdrawn)], [withdrawn.wallet]);
+
+      const batch = [tenant, ...applicants, withdrawn];
+      const before = await Promise.all(applicants.map((applicant) => balance(applicant.wallet.publicKey)));
+      await expectOk([await refundApplicantsIx(listing, batch)], [listing.lessor]);
+
+      for (const [index, applicant] of applicants.entries()) {
+        expect(await balance(applicant.wallet.publicKey)).to.equal(before[index] + REQUIRED_STAKE);
+        expect((await program.account.stakeRecord.fetch(applicant.stakeRecord)).status).to.deep.equal({ settled: {} });
+      }
+      expect((await program.account.stakeRecord.fetch(tenant.stakeRecord)).status).to.deep.equal({ active: {} });
+
+      const escrow = await program.account.apartmentEscrow.fetch(listing.escrow);
+      expect(escrow.activeStakes).to.equal(1);
+      expect(escrow.totalStaked.toNumber()).to.equal(REQUIRED_STAKE);
+    });
+  });
+
+  describe("slash disputes", () => {
+    it("finalizes an undisputed slash once the dispute window closes", async () => {
+      const listing = await createListing();
+      const tenant = await stakedApplicant(listing);
+      const slashDispute = disputePda(listing.apartmentHash, tenant.profileHash);
+      const caller = fundedWallet();
+      await expectOk([await approveIx(listing, tenant)], [listing.lessor]);
+
+      await expectOk([await slashIx(listing, tenant, { amount: [new BN(REQUIRED_STAKE)] })], [listing.lessor]);
+      await expectError([await finalizeIx(listing, tenant, caller)], [caller], "DisputeWindowOpen");
+
+      const { disputeDeadline } = await program.account.slashDispute.fetch(slashDispute);
+      await warpTo(disputeDeadline.toNumber() + 1);
+
+      // Too late to dispute now
+      await expectError([await disputeIx(listing, tenant)], [tenant.wallet], "DisputeWindowClosed");
+
+      const lessorBefore = await balance(listing.lessor.publicKey);
+      const penaltyBefore = await balance(penaltyWallet.publicKey);
+      const disputeRent = await lamportsOf(slashDispute);
+
+      await expectOk([await finalizeIx(listing, tenant, caller)], [caller]);
+
+      const lessorShare = REQUIRED_STAKE * 0.6;
+      expect(await balance(listing.lessor.publicKey)).to.equal(lessorBefore + lessorShare + disputeRent);
+      expect(await balance(penaltyWallet.publicKey)).to.equal(penaltyBefore + REQUIRED_STAKE - lessorShare);
+      expect(await context.banksClient.getAccount(slashDispute)).to.equal(null);
+    });
+
+    it("lets only the staker dispute a slash within the dispute window", async () => {
+      const listing = await createListing();
+      const tenant = await stakedApplicant(listing);
+      const caller = fundedWallet();
+      await expectOk([await approveIx(listing, tenant)], [listing.lessor]);
+      await expectOk([await slashIx(listing, tenant, { amount: [new BN(REQUIRED_STAKE)] })], [listing.lessor]);
+
+      await expectError([await disputeIx(listing, { ...tenant, wallet: caller })], [caller], "UnauthorizedStaker");
+      await expectOk([await disputeIx(listing, tenant)], [tenant.wallet]);
+
+      // A disputed slash waits for a ruling, even after the dispute window
+      const { disputeDeadline, status } = await program.account.slashDispute.fetch(
+        disputePda(listing.apartmentHash, tenant.profileHash)
+      );
+      expect(status).to.deep.equal({ disputed: {} });
+      await warpTo(disputeDeadline.toNumber() + 1);
+      await expectError([await finalizeIx(listing, tenant, caller)], [caller], "InvalidDisputeStatus");
+    });
+  });
+
+  describe("arbiter panel", () => {
+    it("holds a disputed partial slash until the arbiter panel agrees on a ruling", async () => {
+      const listing = await createListing();
+      const tenant = await stakedApplicant(listing);
+      const slashDispute = disputePda(listing.apartmentHash, tenant.profileHash);
+      await expectOk([await approveIx(listing, tenant)], [listing.lessor]);
+
+      // Half the stake is slashed and held, the other half goes straight back to the tenant
+      let tenantBefore = await balance(tenant.wallet.publicKey);
+      await expectOk([await slashIx(listing, tenant, { basisPoints: [5000] })], [listing.lessor]);
+      expect(await balance(tenant.wallet.publicKey)).to.equal(tenantBefore + REQUIRED_STAKE / 2);
+
+      const held = await program.account.slashDispute.fetch(slashDispute);
+      expect(held.amount.toNumber()).to.equal(REQUIRED_STAKE / 2);
+      expect((await program.account.apartmentEscrow.fetch(listing.escrow)).pendingSlashes).to.equal(1);
+
+      await expectOk([await disputeIx(listing, tenant)], [tenant.wallet]);
+
+      // One vote is below the panel's threshold of two, nothing moves
+      await expectOk([await voteIx(listing, tenant, arbiters[0], 2000)], [arbiters[0]]);
+      expect((await program.account.slashDispute.fetch(slashDispute)).votes).to.have.length(1);
+
+      // The lessor's own vote never counts
+      await expectError([await voteIx(listing, tenant, listing.lessor, 2000)], [listing.lessor], "UnauthorizedArbiter");
+
+      tenantBefore = await balance(tenant.wallet.publicKey);
+      const lessorBefore = await balance(listing.lessor.publicKey);
+      const penaltyBefore = await balance(penaltyWallet.publicKey);
+      const disputeRent = await lamportsOf(slashDispute);
+
+      await expectOk([await voteIx(listing, tenant, arbiters[1], 2000)], [arbiters[1]]);
+
+      // 20% of the held half back to the tenant, the rest split 60/40 between the lessor and the penalty wallet
+      const heldAmount = REQUIRED_STAKE / 2;
+      const tenantShare = heldAmount * 0.2;
+      const penalty = heldAmount - tenantShare;
+      const lessorShare = penalty * 0.6;
+      expect(await balance(tenant.wallet.publicKey)).to.equal(tenantBefore + tenantShare);
+      expect(await balance(listing.lessor.publicKey)).to.equal(lessorBefore + lessorShare + disputeRent);
+      expect(await balance(penaltyWallet.publicKey)).to.equal(penaltyBefore + penalty - lessorShare);
+
+      expect(await context.banksClient.getAccount(slashDispute)).to.equal(null);
+      expect((await program.account.apartmentEscrow.fetch(listing.escrow)).pendingSlashes).to.equal(0);
+    });
+  });
+
+  describe("protocol config", () => {
+    const setPenaltyWalletIx = (newPenaltyWallet: PublicKey, signer: Keypair = admin) =>
+      program.methods
+        .setPenaltyWallet(newPenaltyWallet)
+        .accountsPartial({ config: configPda, admin: signer.publicKey })
+        .instruction();
+
+    it("only lets the config admin move the penalty wallet", async () => {
+      const outsider = fundedWallet();
+      const newPenaltyWallet = Keypair.generate().publicKey;
+
+      await expectError([await setPenaltyWalletIx(newPenaltyWallet, outsider)], [outsider], "UnauthorizedAdmin");
+      await expectError([await setPenaltyWalletIx(PublicKey.default)], [admin], "InvalidPenaltyWallet");
+
+      await expectOk([await setPenaltyWalletIx(newPenaltyWallet)], [admin]);
+      expect((await program.account.protocolConfig.fetch(configPda)).penaltyWallet.equals(newPenaltyWallet)).to.equal(true);
+
+      await expectOk([await setPenaltyWalletIx(penaltyWallet.publicKey)], [admin]);
+    });
+  });
+
+  describe("slash split", () => {
+    it("pays every share of a finalized slash atomically, including the referrer's", async () => {
+      const listing = await createListing();
+      const referrer = fundedWallet();
+      const tenant = newApplicant(listing);
+      const caller = fundedWallet();
+      await expectOk(await registerReferralIxs(listing, tenant, referrer, tenant.wallet), [referrer]);
+      await expectOk([await stakeIx(listing, tenant)], [tenant.wallet]);
+      await expectOk([await approveIx(listing, tenant)], [listing.lessor]);
+      await expectOk([await slashIx(listing, tenant, { basisPoints: [10_000] })], [listing.lessor]);
+
+      const { disputeDeadline } = await program.account.slashDispute.fetch(disputePda(listing.apartmentHash, tenant.profileHash));
+      await warpTo(disputeDeadline.toNumber() + 1);
+
+      const lessorBefore = await balance(listing.lessor.publicKey);
+      const penaltyBefore = await balance(penaltyWallet.publicKey);
+      const referrerBefore = await balance(referrer.publicKey);
+      const disputeRent = await lamportsOf(disputePda(listing.apartmentHash, tenant.profileHash));
+
+      await expectOk([await finalizeIx(listing, tenant, caller, referrer.publicKey)], [caller]);
+
+      expect(await balance(listing.lessor.publicKey)).to.equal(lessorBefore + REQUIRED_STAKE * 0.6 + disputeRent);
+      expect(await balance(penaltyWallet.publicKey)).to.equal(penaltyBefore + REQUIRED_STAKE * 0.3);
+      expect(await balance(referrer.publicKey)).to.equal(referrerBefore + REQUIRED_STAKE * 0.1);
+    });
+  });
+
+  describe("resolution fee", () => {
+    const REFERRAL_REWARD = LAMPORTS_PER_SOL / 20;
+
+    it("only takes the fee and referral reward from the approved tenant's stake", async () => {
+      const listing = await createListing({ referralReward: REFERRAL_REWARD, referralLimit: 1 });
+      const referrer = fundedWallet();
+      const tenant = newApplicant(listing);
+      const applicant = newApplicant(listing);
+
+      await expectOk(await registerReferralIxs(listing, tenant, referrer, tenant.wallet), [referrer]);
+      await expectOk(await registerReferralIxs(listing, applicant, referrer, applicant.wallet), [referrer]);
+      await expectOk([await stakeIx(listing, tenant)], [tenant.wallet]);
+      await expectOk([await stakeIx(listing, applicant)], [applicant.wallet]);
+      await expectOk([await approveIx(listing, tenant)], [listing.lessor]);
+
+      const treasuryBefore = await balance(treasuryPda);
+      const referrerBefore = await balance(referrer.publicKey);
+
+      // The applicant who did not get the listing is refunded in full
+      let stakerBefore = await balance(applicant.wallet.publicKey);
+      await expectOk([await resolveIx(listing, applicant, false, referrer.publicKey)], [listing.lessor]);
+      expect(await balance(applicant.wallet.publicKey)).to.equal(stakerBefore + REQUIRED_STAKE);
+      expect(await balance(treasuryPda)).to.equal(treasuryBefore);
+      expect(await balance(referrer.publicKey)).to.equal(referrerBefore);
+
+      stakerBefore = await balance(tenant.wallet.publicKey);
+      await expectOk([await resolveIx(listing, tenant, true, referrer.publicKey)], [listing.lessor]);
+
+      const fee = (REQUIRED_STAKE * RESOLUTION_FEE_BPS) / 10_000;
+      expect(await balance(treasuryPda)).to.equal(treasuryBefore + fee);
+      expect(await balance(referrer.publicKey)).to.equal(referrerBefore + REFERRAL_REWARD);
+      expect(await balance(tenant.wallet.publicKey)).to.equal(stakerBefore + REQUIRED_STAKE - fee - REFERRAL_REWARD);
+
+      const counter = await program.account.referrerCounter.fetch(referrerCounterPda(listing.apartmentHash, referrer.publicKey));
+      expect(counter.referralsPaid).to.equal(1);
+    });
+
+    it("caps the fee and leaves it to the config admin", async () => {
+      const outsider = fundedWallet();
+      const setResolutionFeeIx = (resolutionFeeBps: number, signer: Keypair) =>
+        program.methods
+          .setResolutionFee(resolutionFeeBps)
+          .accountsPartial({ config: configPda, admin: signer.publicKey })
+          .instruction();
+
+      await expectError([await setResolutionFeeIx(RESOLUTION_FEE_BPS, outsider)], [outsider], "UnauthorizedAdmin");
+      await expectError([await setResolutionFeeIx(10_000, admin)], [admin], "ResolutionFeeTooHigh");
+      expect((await program.account.protocolConfig.fetch(configPda)).resolutionFeeBps).to.equal(RESOLUTION_FEE_BPS);
+    });
+  });
+
+  describe("pause flags", () => {
+    const setPausedIx = (paused: boolean, pausePayouts: boolean, signer: Keypair = admin) =>
+      program.methods
+        .setPaused(paused, pausePayouts)
+        .accountsPartial({ config: configPda, admin: signer.publicKey })
+        .instruction();
+
+    afterEach(async () => {
+      await expectOk([await setPausedIx(false, false)], [admin]);
+    });
+
+    it("only lets the config admin pause", async () => {
+      const outsider = fundedWallet();
+      await expectError([await setPausedIx(true, true, outsider)], [outsider], "UnauthorizedAdmin");
+    });
+
+    it("blocks new stakes but keeps payouts and withdrawals open", async () => {
+      const listing = await createListing();
+      const tenant = await stakedApplicant(listing);
+      const applicant = await stakedApplicant(listing);
+      await expectOk([await approveIx(listing, tenant)], [listing.lessor]);
+
+      await expectOk([await setPausedIx(true, false)], [admin]);
+
+      const latecomer = newApplicant(listing);
+      await expectError([await stakeIx(listing, latecomer)], [latecomer.wallet], "ProgramPaused");
+
+      await expectOk([await withdrawIx(listing, applicant)], [applicant.wallet]);
+      await expectOk([await resolveIx(listing, tenant, true)], [listing.lessor]);
+    });
+
+    it("also blocks lessor payouts when the pause covers them, but never staker withdrawals", async () => {
+      const listing = await createListing();
+      const tenant = await stakedApplicant(listing);
+      const applicant = await stakedApplicant(listing);
+      await expectOk([await approveIx(listing, tenant)], [listing.lessor]);
+
+      await expectOk([await setPausedIx(true, true)], [admin]);
+
+      await expectError([await resolveIx(listing, tenant, true)], [listing.lessor], "ProgramPaused");
+      await expectError([await slashIx(listing, tenant, { basisPoints: [10_000] })], [listing.lessor], "ProgramPaused");
+      await expectOk([await withdrawIx(listing, applicant)], [applicant.wallet]);
+
+      const config = await program.account.protocolConfig.fetch(configPda);
+      expect(config.paused).to.equal(true);
+      expect(config.payoutsPaused).to.equal(true);
+    });
+  });
+
+  describe("migrate_account", () => {
+    const migrateIx = (
+      account: PublicKey,
+      legacyEscrow: object | null,
+      options: { lessor?: PublicKey; stakeRecords?: PublicKey[] } = {}
+    ) =>
+      program.methods
+        .migrateAccount(legacyEscrow as any)
+        .accountsPartial({
+          account,
+          payer: context.payer.publicKey,
+          lessor: options.lessor ?? null,
+          arbiterPanel,
+        })
+        .remainingAccounts(
+          (options.stakeRecords ?? []).map((pubkey) => ({ pubkey, isSigner: false, isWritable: false }))
+        )
+        .instruction();
+
+    // A version 0 escrow with one active version 0 stake
+    const legacyListing = async () => {
+      const rent = await context.banksClient.getRent();
+      const lessor = fundedWallet();
+      const apartmentId = uniqueId("apt");
+      const apartmentHash = hash(apartmentId);
+      const [escrow, escrowBump] = escrowPda(apartmentHash);
+
+      const staker = fundedWallet();
+      const profileId = uniqueId("profile");
+      const profileHash = hash(profileId);
+      const [stakeRecord, stakeBump] = stakePda(apartmentHash, profileHash);
+
+      const escrowData = legacyEscrowData(apartmentId, lessor.publicKey, REQUIRED_STAKE, escrowBump);
+      context.setAccount(escrow, {
+        lamports: Number(rent.minimumBalance(BigInt(escrowData.length))) + REQUIRED_STAKE,
+        data: escrowData,
+        owner: PROGRAM_ID,
+        executable: false,
+      });
+      const stakeData = legacyStakeRecordData(profileId, apartmentId, staker.publicKey, REQUIRED_STAKE, stakeBump);
+      context.setAccount(stakeRecord, {
+        lamports: Number(rent.minimumBalance(BigInt(stakeData.length))),
+        data: stakeData,
+        owner: PROGRAM_ID,
+        executable: false,
+      });
+
+      const listing: Listing = { apartmentId, apartmentHash, escrow, lessor };
+      const applicant: Applicant = { profileId, profileHash, wallet: staker, stakeRecord };
+      return { listing, applicant };
+    };
+
+    const legacyTerms = (listing: Listing, profileHashes: number[][], lessorTerms: Partial<Terms> = {}) => ({
+      apartmentHash: listing.apartmentHash,
+      profileHashes,
+      decisionWindow: new BN(lessorTerms.decisionWindow ?? 0),
+      disputeWindow: new BN(lessorTerms.disputeWindow ?? 0),
+      slashSplit: lessorTerms.slashSplit ?? { lessorBps: 0, penaltyWalletBps: 0, referrerBps: 0 },
+    });
+
+    it("requires every active stake record of a legacy escrow", async () => {
+      const { listing } = await legacyListing();
+      await expectError([await migrateIx(listing.escrow, legacyTerms(listing, []))], [], "IncompleteStakeRecords");
+    });
+
+    it("migrates a legacy escrow without its lessor using terms that cannot hurt stakers", async () => {
+      const { listing, applicant } = await legacyListing();
+
+      await expectOk([
+        await migrateIx(listing.escrow, legacyTerms(listing, [applicant.profileHash]), {
+          stakeRecords: [applicant.stakeRecord],
+        }),
+      ]);
+      const migratedAt = await now();
+
+      const escrow = await program.account.apartmentEscrow.fetch(listing.escrow);
+      expect(escrow.version).to.equal(1);
+      expect(escrow.status).to.deep.equal({ open: {} });
+      expect(escrow.activeStakes).to.equal(1);
+      expect(escrow.decisionDeadline.toNumber()).to.equal(migratedAt);
+      expect(escrow.disputeWindow.toNumber()).to.equal(LEGACY_DISPUTE_WINDOW);
+      expect(escrow.slashSplit).to.deep.equal({ lessorBps: 0, penaltyWalletBps: 10_000, referrerBps: 0 });
+      expect(escrow.arbiterPanel.equals(arbiterPanel)).to.equal(true);
+      expect(escrow.rentPayer.equals(listing.lessor.publicKey)).to.equal(true);
+
+      // Migrating twice is refused
+      await expectError(
+        [await migrateIx(listing.escrow, legacyTerms(listing, [applicant.profileHash]), { stakeRecords: [applicant.stakeRecord] })],
+        [],
+        "AccountAlreadyMigrated"
+      );
+
+      // Stake records are refused until they are migrated too
+      await expectError([await withdrawIx(listing, applicant)], [applicant.wallet], "StakeRecordNotMigrated");
+      await expectOk([await migrateIx(applicant.stakeRecord, null)]);
+      expect((await program.account.stakeRecord.fetch(applicant.stakeRecord)).version).to.equal(1);
+
+      // The decision deadline expired at migration, so anyone can refund the staker
+      const caller = fundedWallet();
+      await warpTo(migratedAt + 1);
+      const before = await balance(applicant.wallet.publicKey);
+      await expectOk([await refundExpiredIx(listing, applicant, caller)], [caller]);
+      expect(await balance(applicant.wallet.publicKey)).to.equal(before + REQUIRED_STAKE);
+    });
+
+    it("takes the dispute terms of a legacy escrow from its lessor when they sign", async () => {
+      const { listing, applicant } = await legacyListing();
+      const lessorTerms = {
+        decisionWindow: 2 * ONE_DAY,
+        disputeWindow: ONE_DAY,
+        slashSplit: { lessorBps: 8000, penaltyWalletBps: 2000, referrerBps: 0 },
+      };
+
+      await expectOk(
+        [
+          await migrateIx(listing.escrow, legacyTerms(listing, [applicant.profileHash], lessorTerms), {
+            lessor: listing.lessor.publicKey,
+            stakeRecords: [applicant.stakeRecord],
+          }),
+        ],
+        [listing.lessor]
+      );
+      const migratedAt = await now();
+
+      const escrow = await program.account.apartmentEscrow.fetch(listing.escrow);
+      expect(escrow.decisionDeadline.toNumber()).to.equal(migratedAt + lessorTerms.decisionWindow);
+      expect(escrow.disputeWindow.toNumber()).to.equal(lessorTerms.disputeWindow);
+      expect(escrow.slashSplit).to.deep.equal(lessorTerms.slashSplit);
+    });
+  });
+
+  describe("token stakes", () => {
+    const TOKEN_PROGRAM = new PublicKey("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+    const ASSOCIATED_TOKEN_PROGRAM = new PublicKey("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
+    const DECIMALS = 6;
+    const REQUIRED_TOKENS = 100 * 10 ** DECIMALS;
+
+    const associatedTokenAddress = (owner: PublicKey, mint: PublicKey) =>
+      PublicKey.findProgramAddressSync(
+        [owner.toBuffer(), TOKEN_PROGRAM.toBuffer(), mint.toBuffer()],
+        ASSOCIATED_TOKEN_PROGRAM
+      )[0];
+
+    // Write an initialized SPL token account or mint straight into the bank
+    const setTokenProgramAccount = async (address: PublicKey, data: Buffer) => {
+      const rent = await context.banksClient.getRent();
+      context.setAccount(address, {
+        lamports: Number(rent.minimumBalance(BigInt(data.length))),
+        data,
+        owner: TOKEN_PROGRAM,
+        executable: false,
+      });
+    };
+
+    // Mint { mint_authority: None, supply, decimals, is_initialized, freeze_authority: None }
+    const createMint = async (supply: number) => {
+      const mint = Keypair.generate().publicKey;
+      const data = Buffer.alloc(82);
+      data.writeBigUInt64LE(BigInt(supply), 36);
+      data[44] = DECIMALS;
+      data[45] = 1;
+      await setTokenProgramAccount(mint, data);
+      return mint;
+    };
+
+    // Account { mint, owner, amount, delegate: None, state: Initialized, is_native: None, delegated_amount, close_authority: None }
+    const createTokenAccount = async (mint: PublicKey, owner: PublicKey, amount: number) => {
+      const address = associatedTokenAddress(owner, mint);
+      const data = Buffer.alloc(165);
+      mint.toBuffer().copy(data, 0);
+      owner.toBuffer().copy(data, 32);
+      data.writeBigUInt64LE(BigInt(amount), 64);
+      data[108] = 1;
+      await setTokenProgramAccount(address, data);
+      return address;
+    };
+
+    const tokenBalance = async (address: PublicKey) =>
+      Number(Buffer.from((await context.banksClient.getAccount(address))!.data).readBigUInt64LE(64));
+
+    it("holds token stakes in the escrow's vault and refunds them through the token program", async () => {
+      const mint = await createMint(REQUIRED_TOKENS);
+      const lessor = fundedWallet();
+      const apartmentId = uniqueId("apt");
+      const apartmentHash = hash(apartmentId);
+      const [escrow] = escrowPda(apartmentHash);
+      const stakeVault = associatedTokenAddress(escrow, mint);
+      const listing: Listing = { apartmentId, apartmentHash, escrow, lessor };
+      const tokenAccounts = { stakeMint: mint, stakeVault, tokenProgram: TOKEN_PROGRAM };
+
+      await expectOk(
+        [
+          await program.methods
+            .initializeApartment(
+              apartmentHash,
+              apartmentId,
+              lessor.publicKey,
+              new BN(DEFAULT_TERMS.decisionWindow),
+              new BN(REQUIRED_TOKENS),
+              new BN(0),
+              DEFAULT_TERMS.referralLimit,
+              new BN(DEFAULT_TERMS.disputeWindow),
+              DEFAULT_TERMS.slashSplit,
+              DEFAULT_TERMS.stakeDiscount
+            )
+            .accountsPartial({
+              escrowAccount: escrow,
+              config: configPda,
+              arbiterPanel,
+              initializer: lessor.publicKey,
+              ...tokenAccounts,
+              associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM,
+            })
+            .instruction(),
+        ],
+        [lessor]
+      );
+      expect((await program.account.apartmentEscrow.fetch(escrow)).stakeMint.equals(mint)).to.equal(true);
+
+      const applicant = newApplicant(listing);
+      const stakerTokenAccount = await createTokenAccount(mint, applicant.wallet.publicKey, REQUIRED_TOKENS);
+
+      // A SOL stake into a token escrow is refused
+      await expectError([await stakeIx(listing, applicant, REQUIRED_TOKENS)], [applicant.wallet], "MissingTokenAccount");
+
+      await expectOk(
+        [
+          await program.methods
+            .stakeForApartment(apartmentHash, new BN(REQUIRED_TOKENS), applicant.profileHash, apartmentId, applicant.profileId)
+            .accountsPartial({
+              escrowAccount: escrow,
+              config: configPda,
+              stakeRecord: applicant.stakeRecord,
+              reputation: null,
+              staker: applicant.wallet.publicKey,
+              ...tokenAccounts,
+              stakerTokenAccount,
+            })
+            .instruction(),
+        ],
+        [applicant.wallet]
+      );
+      expect(await tokenBalance(stakeVault)).to.equal(REQUIRED_TOKENS);
+      expect(await tokenBalance(stakerTokenAccount)).to.equal(0);
+      expect((await program.account.apartmentEscrow.fetch(escrow)).totalStaked.toNumber()).to.equal(REQUIRED_TOKENS);
+
+      await expectOk(
+        [
+          await program.methods
+            .withdrawStake(apartmentHash, applicant.profileHash, apartmentId, applicant.profileId)
+            .accountsPartial({
+              escrowAccount: escrow,
+              stakeRecord: applicant.stakeRecord,
+              staker: applicant.wallet.publicKey,
+              ...tokenAccounts,
+              stakerTokenAccount,
+            })
+            .instruction(),
+        ],
+        [applicant.wallet]
+      );
+      expect(await tokenBalance(stakeVault)).to.equal(0);
+      expect(await tokenBalance(stakerTokenAccount)).to.equal(REQUIRED_TOKENS);
+    });
+  });
+
+  describe("lifecycle phases", () => {
+    it("stops the approved tenant's stake from expiring once the lease starts", async () => {
+      const listing = await createListing();
+      const tenant = await stakedApplicant(listing);
+      const caller = fundedWallet();
+      await expectOk([await approveIx(listing, tenant), await startLeaseIx(listing)], [listing.lessor]);
+
+      const { decisionDeadline, status } = await program.account.apartmentEscrow.fetch(listing.escrow);
+      expect(status).to.deep.equal({ leaseActive: {} });
+      await warpTo(decisionDeadline.toNumber() + 1);
+      await expectError([await refundExpiredIx(listing, tenant, caller)], [caller], "InvalidEscrowStatus");
+
+      // Nor can the lessor close the listing while the tenant lives there
+      await expectError([await closeEscrowIx(listing)], [listing.lessor], "InvalidEscrowStatus");
+    });
+
+    it("closes the listing once the approved tenant's stake is settled", async () => {
+      const listing = await createListing();
+      const tenant = await stakedApplicant(listing);
+      await expectOk([await approveIx(listing, tenant)], [listing.lessor]);
+      await expectError([await closeEscrowIx(listing)], [listing.lessor], "InvalidEscrowStatus");
+
+      await expectOk([await resolveIx(listing, tenant, true)], [listing.lessor]);
+      expect((await program.account.apartmentEscrow.fetch(listing.escrow)).status).to.deep.equal({ closed: {} });
+
+      await expectOk([await closeStakeRecordIx(listing, tenant)], [tenant.wallet]);
+      await expectOk([await closeEscrowIx(listing)], [listing.lessor]);
+      expect(await context.banksClient.getAccount(listing.escrow)).to.equal(null);
+    });
+
+    it("skips stake records that still need migrating in a bulk refund", async () => {
+      const listing = await createListing();
+      const tenant = await stakedApplicant(listing);
+      const applicant = await stakedApplicant(listing);
+      await expectError([await refundApplicantsIx(listing, [applicant])], [listing.lessor], "InvalidEscrowStatus");
+      await expectOk([await approveIx(listing, tenant)], [listing.lessor]);
+
+      // A version 0 record left behind under this listing
+      const legacy = newApplicant(listing);
+      const [, legacyBump] = stakePda(listing.apartmentHash, legacy.profileHash);
+      const legacyData = legacyStakeRecordData(
+        legacy.profileId,
+        listing.apartmentId,
+        legacy.wallet.publicKey,
+        REQUIRED_STAKE,
+        legacyBump
+      );
+      const rent = await context.banksClient.getRent();
+      context.setAccount(legacy.stakeRecord, {
+        lamports: Number(rent.minimumBalance(BigInt(legacyData.length))),
+        data: legacyData,
+        owner: PROGRAM_ID,
+        executable: false,
+      });
+
+      const before = await balance(applicant.wallet.publicKey);
+      await expectOk([await refundApplicantsIx(listing, [legacy, applicant])], [listing.lessor]);
+      expect(await balance(applicant.wallet.publicKey)).to.equal(before + REQUIRED_STAKE);
+      expect((await context.banksClient.getAccount(legacy.stakeRecord))?.data.length).to.equal(legacyData.length);
+    });
+  });
+
+  describe("security deposit", () => {
+    const convertIx = (listing: Listing, tenant: Applicant, leaseEnd: number) =>
+      program.methods
+        .convertToDeposit(listing.apartmentHash, tenant.profileHash, listing.apartmentId, tenant.profileId, new BN(leaseEnd))
+        .accountsPartial({ escrowAccount: listing.escrow, stakeRecord: tenant.stakeRecord, lessor: listing.lessor.publicKey })
+        .instruction();
+
+    const beginMoveOutIx = (listing: Listing, tenant: Applicant) =>
+      program.methods
+        .beginMoveOut(listing.apartmentHash, listing.apartmentId, tenant.profileHash)
+        .accountsPartial({ escrowAccount: listing.escrow, stakeRecord: tenant.stakeRecord, lessor: listing.lessor.publicKey })
+        .instruction();
+
+    const settleMoveOutIx = (listing: Listing, tenant: Applicant, deductions: object[]) =>
+      program.methods
+        .settleMoveOut(listing.apartmentHash, tenant.profileHash, deductions as any)
+        .accountsPartial({
+          escrowAccount: listing.escrow,
+          stakeRecord: tenant.stakeRecord,
+          moveOutSettlement: moveOutPda(listing.apartmentHash, tenant.profileHash),
+          lessor: listing.lessor.publicKey,
+        })
+        .instruction();
+
+    const releaseDepositIx = (listing: Listing, tenant: Applicant, caller: Keypair) =>
+      program.methods
+        .releaseDeposit(listing.apartmentHash, tenant.profileHash)
+        .accountsPartial({
+          escrowAccount: listing.escrow,
+          stakeRecord: tenant.stakeRecord,
+          moveOutSettlement: moveOutPda(listing.apartmentHash, tenant.profileHash),
+          config: configPda,
+          staker: tenant.wallet.publicKey,
+          lessor: listing.lessor.publicKey,
+          rentPayer: listing.lessor.publicKey,
+          reputation: reputationPda(tenant.profileHash, tenant.wallet.publicKey),
+          caller: caller.publicKey,
+          stakeMint: null,
+          stakeVault: null,
+          stakerTokenAccount: null,
+          lessorTokenAccount: null,
+          tokenProgram: null,
+        })
+        .instruction();
+
+    const refundUnsettledIx = (listing: Listing, tenant: Applicant, caller: Keypair) =>
+      program.methods
+        .refundUnsettledDeposit(listing.apartmentHash, tenant.profileHash)
+        .accountsPartial({
+          escrowAccount: listing.escrow,
+          stakeRecord: tenant.stakeRecord,
+          moveOutSettlement: moveOutPda(listing.apartmentHash, tenant.profileHash),
+          staker: tenant.wallet.publicKey,
+          caller: caller.publicKey,
+          stakeMint: null,
+          stakeVault: null,
+          stakerTokenAccount: null,
+          tokenProgram: null,
+        })
+        .instruction();
+
+    const cleaning = (amount: number) => ({
+      category: { cleaning: {} },
+      amount: new BN(amount),
+      evidenceHash: hash("cleaning invoice"),
+      uri: "https://example.com/invoices/cleaning.pdf",
+    });
+
+    it("holds the tenant's stake as a deposit until the tenant accepts the move-out deductions", async () => {
+      const listing = await createListing();
+      const tenant = await leasedTenant(listing);
+      const leaseEnd = (await now()) + 30 * ONE_DAY;
+
+      await expectError([await convertIx(listing, tenant, (await now()) - 1)], [listing.lessor], "InvalidLeaseEnd");
+      await expectOk([await convertIx(listing, tenant, leaseEnd)], [listing.lessor]);
+
+      // A deposit is no longer an application stake
+      await expectError([await resolveIx(listing, tenant, true)], [listing.lessor], "StakeNotActive");
+      await expectError([await settleMoveOutIx(listing, tenant, [cleaning(1)])], [listing.lessor], "InvalidEscrowStatus");
+
+      await warpTo(leaseEnd);
+      await expectOk([await beginMoveOutIx(listing, tenant)], [listing.lessor]);
+      await expectError(
+        [await settleMoveOutIx(listing, tenant, [cleaning(REQUIRED_STAKE + 1)])],
+        [listing.lessor],
+        "DeductionsExceedDeposit"
+      );
+
+      const deduction = REQUIRED_STAKE / 4;
+      await expectOk([await settleMoveOutIx(listing, tenant, [cleaning(deduction)])], [listing.lessor]);
+      const settlement = await program.account.moveOutSettlement.fetch(moveOutPda(listing.apartmentHash, tenant.profileHash));
+      expect(settlement.totalDeductions.toNumber()).to.equal(deduction);
+      expect(settlement.deductions[0].category).to.deep.equal({ cleaning: {} });
+
+      // Only the tenant can release before the response window lapses
+      const caller = fundedWallet();
+      await expectError([await releaseDepositIx(listing, tenant, caller)], [caller], "ResponseWindowOpen");
+
+      const lessorBefore = await balance(listing.lessor.publicKey);
+      const tenantBefore = await balance(tenant.wallet.publicKey);
+      const settlementRent = await lamportsOf(moveOutPda(listing.apartmentHash, tenant.profileHash));
+      await expectOk([await releaseDepositIx(listing, tenant, tenant.wallet)], [tenant.wallet]);
+
+      const reputationRent = await lamportsOf(reputationPda(tenant.profileHash, tenant.wallet.publicKey));
+      expect(await balance(listing.lessor.publicKey)).to.equal(lessorBefore + deduction + settlementRent);
+      expect(await balance(tenant.wallet.publicKey)).to.equal(tenantBefore + REQUIRED_STAKE - deduction - reputationRent);
+      expect((await program.account.stakeRecord.fetch(tenant.stakeRecord)).status).to.deep.equal({ settled: {} });
+      expect((await program.account.apartmentEscrow.fetch(listing.escrow)).status).to.deep.equal({ closed: {} });
+    });
+
+    it("refunds the whole deposit when the lessor proposes no deductions in time", async () => {
+      const listing = await createListing();
+      const tenant = await leasedTenant(listing);
+      const caller = fundedWallet();
+      const leaseEnd = (await now()) + 30 * ONE_DAY;
+      await expectOk([await convertIx(listing, tenant, leaseEnd)], [listing.lessor]);
+
+      await warpTo(leaseEnd + DEFAULT_TERMS.disputeWindow);
+      await expectError([await refundUnsettledIx(listing, tenant, caller)], [caller], "MoveOutWindowOpen");
+
+      await warpTo(leaseEnd + DEFAULT_TERMS.disputeWindow + 1);
+      const before = await balance(tenant.wallet.publicKey);
+      await expectOk([await refundUnsettledIx(listing, tenant, caller)], [caller]);
+      expect(await balance(tenant.wallet.publicKey)).to.equal(before + REQUIRED_STAKE);
+      expect((await program.account.apartmentEscrow.fetch(listing.escrow)).status).to.deep.equal({ closed: {} });
+    });
+  });
+
+  describe("itemized deductions", () => {
+    const lineItem = (category: object, amount: number, evidence: string) => ({
+      category,
+      amount: new BN(amount),
+      evidenceHash: hash(evidence),
+      uri: `https://example.com/evidence/${evidence}`,
+    });
+
+    it("keeps the line items of a slash for the tenant to inspect, as long as they itemize all of it", async () => {
+      const listing = await createListing();
+      const tenant = await stakedApplicant(listing);
+      await expectOk([await approveIx(listing, tenant)], [listing.lessor]);
+
+      const slashed = REQUIRED_STAKE / 2;
+      const lineItems = [
+        lineItem({ damage: {} }, (slashed / 5) * 4, "broken-window.jpg"),
+        lineItem({ cleaning: {} }, slashed / 5, "cleaning-invoice.pdf"),
+      ];
+
+      await expectError(
+        [await slashIx(listing, tenant, { amount: [new BN(slashed)] }, lineItems.slice(0, 1))],
+        [listing.lessor],
+        "LineItemsMismatch"
+      );
+      await expectOk([await slashIx(listing, tenant, { amount: [new BN(slashed)] }, lineItems)], [listing.lessor]);
+
+      const slashDispute = await program.account.slashDispute.fetch(disputePda(listing.apartmentHash, tenant.profileHash));
+      expect(slashDispute.lineItems).to.have.length(2);
+      expect(slashDispute.lineItems[0].category).to.deep.equal({ damage: {} });
+      expect(slashDispute.lineItems[0].amount.toNumber()).to.equal((slashed / 5) * 4);
+      expect(slashDispute.lineItems[0].evidenceHash).to.deep.equal(hash("broken-window.jpg"));
+      expect(slashDispute.lineItems[1].uri).to.equal("https://example.com/evidence/cleaning-invoice.pdf");
+    });
+  });
+
+  describe("mutual_release", () => {
+    it("splits the tenant's stake as both sides agreed, without a dispute window", async () => {
+      const listing = await createListing();
+      const tenant = await leasedTenant(listing);
+      const referrer = fundedWallet();
+      const signers = [listing.lessor, tenant.wallet];
+
+      await expectError([await mutualReleaseIx(listing, tenant, REQUIRED_STAKE + 1)], signers, "InvalidReleaseSplit");
+      // Nobody referred this tenant, so there is no referrer to pay
+      await expectError(
+        [await mutualReleaseIx(listing, tenant, 0, REQUIRED_STAKE / 10, referrer.publicKey)],
+        signers,
+        "InvalidReferral"
+      );
+
+      const lessorAmount = (REQUIRED_STAKE / 10) * 3;
+      const lessorBefore = await balance(listing.lessor.publicKey);
+      const tenantBefore = await balance(tenant.wallet.publicKey);
+      await expectOk([await mutualReleaseIx(listing, tenant, lessorAmount)], signers);
+
+      expect(await balance(listing.lessor.publicKey)).to.equal(lessorBefore + lessorAmount);
+      expect(await balance(tenant.wallet.publicKey)).to.equal(tenantBefore + REQUIRED_STAKE - lessorAmount);
+      expect(await context.banksClient.getAccount(disputePda(listing.apartmentHash, tenant.profileHash))).to.equal(null);
+      expect((await program.account.stakeRecord.fetch(tenant.stakeRecord)).status).to.deep.equal({ settled: {} });
+      expect((await program.account.apartmentEscrow.fetch(listing.escrow)).status).to.deep.equal({ closed: {} });
+    });
+
+    it("needs the staker's own signature next to the lessor's", async () => {
+      const listing = await createListing();
+      const applicant = await stakedApplicant(listing);
+      const outsider = fundedWallet();
+
+      await expectError(
+        [await mutualReleaseIx(listing, { ...applicant, wallet: outsider }, 0)],
+        [listing.lessor, outsider],
+        "UnauthorizedStaker"
+      );
+      await expectError(
+        [await mutualReleaseIx({ ...listing, lessor: outsider }, applicant, 0)],
+        [outsider, applicant.wallet],
+        "UnauthorizedLessor"
+      );
+      expect((await program.account.stakeRecord.fetch(applicant.stakeRecord)).status).to.deep.equal({ active: {} });
+    });
+  });
+
+  describe("reputation", () => {
+    it("counts the approved tenant's stake once their lease ran at least 30 days", async () => {
+      const listing = await createListing();
+      const tenant = await leasedTenant(listing);
+      const leaseStartedAt = await now();
+
+      await warpTo(leaseStartedAt + 30 * ONE_DAY);
+      await expectOk([await resolveIx(listing, tenant, true)], [listing.lessor]);
+
+      const reputation = await program.account.reputation.fetch(reputationPda(tenant.profileHash, tenant.wallet.publicKey));
+      expect(reputation.owner.equals(tenant.wallet.publicKey)).to.equal(true);
+      expect(reputation.completedStakes).to.equal(1);
+      expect(reputation.totalResolved.toNumber()).to.equal(REQUIRED_STAKE);
+      expect(reputation.slashCount).to.equal(0);
+    });
+
+    it("does not count a lease that ended early", async () => {
+      const listing = await createListing();
+      const tenant = await leasedTenant(listing);
+
+      await warpTo((await now()) + 30 * ONE_DAY - 1);
+      await expectOk([await resolveIx(listing, tenant, true)], [listing.lessor]);
+
+      const reputation = await program.account.reputation.fetch(reputationPda(tenant.profileHash, tenant.wallet.publicKey));
+      expect(reputation.completedStakes).to.equal(0);
+      expect(reputation.totalResolved.toNumber()).to.equal(0);
+    });
+
+    it("records a slash once it stood through the dispute window", async () => {
+      const listing = await createListing();
+      const tenant = await stakedApplicant(listing);
+      const caller = fundedWallet();
+      await expectOk([await approveIx(listing, tenant)], [listing.lessor]);
+      await expectOk([await slashIx(listing, tenant, { basisPoints: [2500] })], [listing.lessor]);
+
+      const reputation = reputationPda(tenant.profileHash, tenant.wallet.publicKey);
+      expect((await program.account.reputation.fetch(reputation)).slashCount).to.equal(0);
+
+      const { disputeDeadline } = await program.account.slashDispute.fetch(disputePda(listing.apartmentHash, tenant.profileHash));
+      await warpTo(disputeDeadline.toNumber() + 1);
+      await expectOk([await finalizeIx(listing, tenant, caller)], [caller]);
+
+      const { slashCount, totalSlashed } = await program.account.reputation.fetch(reputation);
+      expect(slashCount).to.equal(1);
+      expect(totalSlashed.toNumber()).to.equal(REQUIRED_STAKE / 4);
+    });
+  });
+
+  describe("stake discount", () => {
+    it("lowers the required stake for a tenant who completed a lease without being slashed", async () => {
+      const previousListing = await createListing();
+      const tenant = await leasedTenant(previousListing);
+      await warpTo((await now()) + 30 * ONE_DAY);
+      await expectOk([await resolveIx(previousListing, tenant, true)], [previousListing.lessor]);
+      const reputation = reputationPda(tenant.profileHash, tenant.wallet.publicKey);
+
+      // 10% off per completed lease, capped at 20%
+      const listing = await createListing({ stakeDiscount: { bpsPerCompletedStake: 1000, maxDiscountBps: 2000 } });
+      const applicant = { ...tenant, stakeRecord: stakePda(listing.apartmentHash, tenant.profileHash)[0] };
+      const discountedStake = (REQUIRED_STAKE / 10) * 9;
+
+      await expectError([await stakeIx(listing, applicant, discountedStake)], [tenant.wallet], "StakeBelowMinimum");
+      await expectError(
+        [await stakeIx(listing, applicant, discountedStake - 1, reputation)],
+        [tenant.wallet],
+        "StakeBelowMinimum"
+      );
+      await expectOk([await stakeIx(listing, applicant, discountedStake, reputation)], [tenant.wallet]);
+      expect((await program.account.stakeRecord.fetch(applicant.stakeRecord)).amount.toNumber()).to.equal(discountedStake);
+    });
+
+    it("gives no discount once the tenant's profile was slashed", async () => {
+      const completedListing = await createListing();
+      const tenant = await leasedTenant(completedListing);
+      await warpTo((await now()) + 30 * ONE_DAY);
+      await expectOk([await resolveIx(completedListing, tenant, true)], [completedListing.lessor]);
+
+      // The same profile is slashed on its next listing
+      const slashedListing = await createListing();
+      const slashed = { ...tenant, stakeRecord: stakePda(slashedListing.apartmentHash, tenant.profileHash)[0] };
+      const caller = fundedWallet();
+      await expectOk([await stakeIx(slashedListing, slashed)], [tenant.wallet]);
+      await expectOk([await approveIx(slashedListing, slashed)], [slashedListing.lessor]);
+      await expectOk([await slashIx(slashedListing, slashed, { basisPoints: [1000] })], [slashedListing.lessor]);
+      const { disputeDeadline } = await program.account.slashDispute.fetch(
+        disputePda(slashedListing.apartmentHash, tenant.profileHash)
+      );
+      await warpTo(disputeDeadline.toNumber() + 1);
+      await expectOk([await finalizeIx(slashedListing, slashed, caller)], [caller]);
+
+      const reputation = reputationPda(tenant.profileHash, tenant.wallet.publicKey);
+      expect((await program.account.reputation.fetch(reputation)).completedStakes).to.equal(1);
+
+      const listing = await createListing({ stakeDiscount: { bpsPerCompletedStake: 1000, maxDiscountBps: 2000 } });
+      const applicant = { ...tenant, stakeRecord: stakePda(listing.apartmentHash, tenant.profileHash)[0] };
+      await expectError(
+        [await stakeIx(listing, applicant, REQUIRED_STAKE - 1, reputation)],
+        [tenant.wallet],
+        "StakeBelowMinimum"
+      );
+    });
+  });
+});
@@ -11,6 +11,5 @@ export default  function EscrowPage() {
   const params = useParams();
   const apartmentId = params.apartmentId as string;
 
-  // return <EscrowOperations apartmentId={apartmentId}/>;
   return <CleanGillEscrowOperations apartmentId={apartmentId}/>;
 } 
\ No newline at end of file
@@ -1,5 +1,5 @@
 import React, { useState, useEffect, useCallback } from 'react';
-import { useWallet } from '@solana/wallet-adapter-react';
+import { useConnection, useWallet } from '@solana/wallet-adapter-react';
 import { useProfile } from '@/contexts/ProfileContext';
 import { supabase } from '@/lib/supabase';
 import { Apartment, Profile } from '@/lib/schema';
@@ -9,10 +9,19 @@ import { getApartmentById } from '@/lib/database';
 import {
   initializeApartment,
   stakeForApartment,
+  withdrawStake,
+  signReferral,
+  submitReferral,
   resolveStake,
   slashStake,
+  approveTenant,
+  finalizeSlash,
+  fetchSlashDisputes,
+  startLease,
   fetchStakeRecords,
-  checkEscrowExists
+  fetchEscrow,
+  EscrowTerms,
+  SlashTerms
 } from './operations/escrowOperations';
 
 // Import page components
@@ -27,6 +36,7 @@ interface CleanGillEscrowOperationsProps {
 }
 
 export const CleanGillEscrowOperations: React.FC<CleanGillEscrowOperationsProps> = ({ apartmentId }) => {
+  const { connection } = useConnection();
   const wallet = useWallet();
   const { profile } = useProfile();
   
@@ -39,9 +49,14 @@ export const CleanGillEscrowOperations: React.FC<CleanGillEscrowOperationsProps>
   const [approvedProfile, setApprovedProfile] = useState<Profile | null>(null);
   const [escrowData, setEscrowData] = useState<any>(null);
   const [stakeRecords, setStakeRecords] = useState<any[]>([]);
+  const [slashDisputes, setSlashDisputes] = useState<any[]>([]);
   const [stakeAmount, setStakeAmount] = useState('');
   const [hasAccess, setHasAccess] = useState<boolean | null>(null);
   const [referrerPubkey, setReferrerPubkey] = useState<string | null>(null);
+  const [signedReferral, setSignedReferral] = useState<string | null>(null);
+
+  // Referrer of the approved profile submits its on-chain referral
+  const isReferrer = Boolean(referrerPubkey && referrerPubkey === wallet.publicKey?.toBase58());
 
   // Helper function to check if current profile has access
   const checkAccess = useCallback((): boolean => {
@@ -50,8 +65,8 @@ export const CleanGillEscrowOperations: React.FC<CleanGillEscrowOperationsProps>
     const isOwner = apartment?.owner === profile.id;
     const isApproved = apartment?.approved_profile === profile.id;
 
-    return isOwner || isApproved;
-  }, [profile, apartment, wallet.publicKey]);
+    return isOwner || isApproved || isReferrer;
+  }, [profile, apartment, wallet.publicKey, isReferrer]);
 
   // Check if current user is the apartment owner
   const isOwner = profile &&
@@ -110,20 +125,24 @@ export const CleanGillEscrowOperations: React.FC<CleanGillEscrowOperationsProps>
         setApprovedProfile(approvedProfileData);
       }
 
-      // Check if escrow exists
-      const escrowExists = await checkEscrowExists(apartmentId);
-      setEscrowData(escrowExists ? { exists: true } : null);
+      // Fetch escrow state, null until the owner initializes it
+      const escrow = await fetchEscrow(apartmentId, connection);
+      setEscrowData(escrow);
 
       // Fetch stake records for this apartment
-      const stakes = await fetchStakeRecords(apartmentId);
+      const stakes = await fetchStakeRecords(apartmentId, connection);
       setStakeRecords(stakes);
 
+      // Slashed stakes keep their funds in the escrow until the slash is finalized or ruled on
+      const disputes = await fetchSlashDisputes(apartmentId, stakes, connection);
+      setSlashDisputes(disputes);
+
     } catch (error) {
       console.error('Error fetching data:', error);
     } finally {
       setDataLoading(false);
     }
-  }, [apartmentId]);
+  }, [apartmentId, connection]);
 
   // Effects
   useEffect(() => {
@@ -139,15 +158,15 @@ export const CleanGillEscrowOperations: React.FC<CleanGillEscrowOperationsProps>
   }, [fetchData]);
 
   // Handlers
-  const handleInitialize = async () => {
-    if (!apartmentOwnerProfile?.pubkey) {
+  const handleInitialize = async (terms: EscrowTerms) => {
+    if (!apartmentOwnerProfile?.pubkey || !apartment) {
       console.log('Missing required data for initialization');
       return;
     }
 
     setInitializing(true);
     try {
-      const signature = await initializeApartment(apartmentId, apartmentOwnerProfile.pubkey, wallet);
+      const signature = await initializeApartment(apartmentId, apartmentOwnerProfile.pubkey, terms, wallet, connection);
       console.log('Initialize tx:', signature);
       await fetchData();
     } catch (error) {
@@ -165,8 +184,7 @@ export const CleanGillEscrowOperations: React.FC<CleanGillEscrowOperationsProps>
 
     setLoading(true);
     try {
-      const amount = parseFloat(stakeAmount);
-      const signature = await stakeForApartment(apartmentId, amount, profile.id, wallet);
+      const signature = await stakeForApartment(apartmentId, stakeAmount, profile.id, wallet, connection);
       console.log('Stake tx:', signature);
       setStakeAmount('');
       await fetchData();
@@ -177,27 +195,79 @@ export const CleanGillEscrowOperations: React.FC<CleanGillEscrowOperationsProps>
     }
   };
 
+  const handleSignReferral = async () => {
+    if (!profile?.id || !referrerPubkey) return;
+
+    setLoading(true);
+    try {
+      const signed = await signReferral(apartmentId, profile.id, referrerPubkey, wallet);
+      setSignedReferral(signed);
+    } catch (error) {
+      console.error('Error signing referral:', error);
+    } finally {
+      setLoading(false);
+    }
+  };
+
+  const handleSubmitReferral = async (signed: string) => {
+    setLoading(true);
+    try {
+      const signature = await submitReferral(apartmentId, signed, wallet, connection);
+      console.log('Referral tx:', signature);
+      await fetchData();
+    } catch (error) {
+      console.error('Error submitting referral:', error);
+    } finally {
+      setLoading(false);
+    }
+  };
+
+  const handleWithdraw = async (stakeRecord: any) => {
+    setLoading(true);
+    try {
+      const signature = await withdrawStake(apartmentId, stakeRecord, wallet, connection);
+      console.log('Withdraw tx:', signature);
+      await fetchData();
+    } catch (error) {
+      console.error('Error withdrawing stake:', error);
+    } finally {
+      setLoading(false);
+    }
+  };
+
+  const handleApprove = async (stakeRecord: any) => {
+    setLoading(true);
+    try {
+      const signature = await approveTenant(apartmentId, stakeRecord, wallet, connection);
+      console.log('Approve tx:', signature);
+      await fetchData();
+    } catch (error) {
+      console.error('Error approving tenant:', error);
+    } finally {
+      setLoading(false);
+    }
+  };
+
+  const handleStartLease = async () => {
+    setLoading(true);
+    try {
+      const signature = await startLease(apartmentId, wallet, connection);
+      console.log('Start lease tx:', signature);
+      await fetchData();
+    } catch (error) {
+      console.error('Error starting lease:', error);
+    } finally {
+      setLoading(false);
+    }
+  };
+
   const handleResolve = async (stakeRecord: any) => {
     if (!apartmentOwnerProfile?.pubkey) return;
 
     setLoading(true);
     try {
-      const rewardAmount = apartment?.reward || 0;
-      
-      console.log('=== HANDLE RESOLVE DEBUG ===');
-      console.log('apartmentOwnerProfile:', apartmentOwnerProfile);
-      console.log('apartmentOwnerProfile.pubkey:', apartmentOwnerProfile.pubkey);
-      console.log('referrerPubkey:', referrerPubkey);
-      console.log('rewardAmount:', rewardAmount);
-      
-      const signature = await resolveStake(
-        apartmentId, 
-        stakeRecord, 
-        apartmentOwnerProfile.pubkey, 
-        referrerPubkey, 
-        rewardAmount, 
-        wallet
-      );
+      // The program pays the referrer the tenant registered on-chain, at the escrow's reward
+      const signature = await resolveStake(apartmentId, stakeRecord, apartmentOwnerProfile.pubkey, wallet, connection);
       console.log('Resolve tx:', signature);
       await fetchData();
     } catch (error) {
@@ -207,12 +277,12 @@ export const CleanGillEscrowOperations: React.FC<CleanGillEscrowOperationsProps>
     }
   };
 
-  const handleSlash = async (stakeRecord: any) => {
+  const handleSlash = async (stakeRecord: any, terms: SlashTerms) => {
     if (!apartmentOwnerProfile?.pubkey) return;
 
     setLoading(true);
     try {
-      const signature = await slashStake(apartmentId, stakeRecord, apartmentOwnerProfile.pubkey, wallet);
+      const signature = await slashStake(apartmentId, stakeRecord, apartmentOwnerProfile.pubkey, terms, wallet, connection);
       console.log('Slash tx:', signature);
       await fetchData();
     } catch (error) {
@@ -222,6 +292,21 @@ export const CleanGillEscrowOperations: React.FC<CleanGillEscrowOperationsProps>
     }
   };
 
+  const handleFinalizeSlash = async (slashDispute: any) => {
+    if (!escrowData?.lessor) return;
+
+    setLoading(true);
+    try {
+      const signature = await finalizeSlash(apartmentId, slashDispute, escrowData.lessor.toBase58(), wallet, connection);
+      console.log('Finalize slash tx:', signature);
+      await fetchData();
+    } catch (error) {
+      console.error('Error finalizing slash:', error);
+    } finally {
+      setLoading(false);
+    }
+  };
+
   // Render appropriate page based on state
   if (!wallet.connected) {
     return <ConnectWalletPage />;
@@ -264,15 +349,24 @@ export const CleanGillEscrowOperations: React.FC<CleanGillEscrowOperationsProps>
       profile={profile}
       escrowData={escrowData}
       stakeRecords={stakeRecords}
+      slashDisputes={slashDisputes}
       stakeAmount={stakeAmount}
       setStakeAmount={setStakeAmount}
       loading={loading}
       isOwner={Boolean(isOwner)}
       referrerPubkey={referrerPubkey}
+      isReferrer={isReferrer}
+      signedReferral={signedReferral}
       onRefresh={fetchData}
       onStake={handleStake}
+      onSignReferral={handleSignReferral}
+      onSubmitReferral={handleSubmitReferral}
+      onWithdraw={handleWithdraw}
+      onApprove={handleApprove}
+      onStartLease={handleStartLease}
       onResolve={handleResolve}
       onSlash={handleSlash}
+      onFinalizeSlash={handleFinalizeSlash}
     />
   );
 }; 
\ No newline at end of file
@@ -0,0 +1,148 @@
+import React, { useState } from 'react';
+import {
+  SlashTerms,
+  SlashLineItem,
+  SLASH_REASONS,
+  DEDUCTION_CATEGORIES
+} from './operations/escrowOperations';
+
+interface SlashStakeFormProps {
+  loading: boolean;
+  onSubmit: (terms: SlashTerms) => void;
+  onCancel: () => void;
+}
+
+const emptyLineItem = (): SlashLineItem => ({
+  category: 'damage',
+  amount: '',
+  uri: '',
+  evidenceHash: null,
+});
+
+// Hash an evidence file the same way the program stores it (sha256)
+const hashEvidence = async (file: File): Promise<Uint8Array> =>
+  new Uint8Array(await crypto.subtle.digest('SHA-256', await file.arrayBuffer()));
+
+export const SlashStakeForm: React.FC<SlashStakeFormProps> = ({ loading, onSubmit, onCancel }) => {
+  const [terms, setTerms] = useState<SlashTerms>({
+    amountMode: 'percent',
+    amount: '100',
+    reason: 'other',
+    lineItems: [],
+  });
+
+  const updateLineItem = (index: number, update: Partial<SlashLineItem>) => {
+    setTerms((current) => ({
+      ...current,
+      lineItems: current.lineItems.map((item, i) => (i === index ? { ...item, ...update } : item)),
+    }));
+  };
+
+  const removeLineItem = (index: number) => {
+    setTerms((current) => ({
+      ...current,
+      lineItems: current.lineItems.filter((_, i) => i !== index),
+    }));
+  };
+
+  const itemized = terms.lineItems.length > 0;
+
+  return (
+    <div className="mt-3 space-y-3 border-t pt-3 text-sm">
+      <div className="flex gap-2">
+        <select
+          value={terms.amountMode}
+          onChange={(e) => setTerms({ ...terms, amountMode: e.target.value as SlashTerms['amountMode'] })}
+          disabled={itemized}
+          className="px-2 py-1 border rounded"
+        >
+          <option value="percent">% of stake</option>
+          <option value="sol">SOL</option>
+        </select>
+        <input
+          type="text"
+          inputMode="decimal"
+          value={itemized ? '' : terms.amount}
+          onChange={(e) => setTerms({ ...terms, amount: e.target.value })}
+          placeholder={itemized ? 'Total of the line items' : 'Amount'}
+          disabled={itemized}
+          className="flex-1 px-2 py-1 border rounded"
+        />
+        <select
+          value={terms.reason}
+          onChange={(e) => setTerms({ ...terms, reason: e.target.value as SlashTerms['reason'] })}
+          className="px-2 py-1 border rounded"
+        >
+          {SLASH_REASONS.map((reason) => (
+            <option key={reason} value={reason}>{reason}</option>
+          ))}
+        </select>
+      </div>
+
+      {terms.lineItems.map((item, index) => (
+        <div key={index} className="flex flex-wrap gap-2 items-center">
+          <select
+            value={item.category}
+            onChange={(e) => updateLineItem(index, { category: e.target.value as SlashLineItem['category'] })}
+            className="px-2 py-1 border rounded"
+          >
+            {DEDUCTION_CATEGORIES.map((category) => (
+              <option key={category} value={category}>{category}</option>
+            ))}
+          </select>
+          <input
+            type="text"
+            inputMode="decimal"
+            value={item.amount}
+            onChange={(e) => updateLineItem(index, { amount: e.target.value })}
+            placeholder="SOL"
+            className="w-24 px-2 py-1 border rounded"
+          />
+          <input
+            type="url"
+            value={item.uri}
+            onChange={(e) => updateLineItem(index, { uri: e.target.value })}
+            placeholder="Evidence link"
+            className="flex-1 px-2 py-1 border rounded"
+          />
+          <input
+            type="file"
+            onChange={async (e) => {
+              const file = e.target.files?.[0];
+              updateLineItem(index, { evidenceHash: file ? await hashEvidence(file) : null });
+            }}
+            className="text-xs"
+          />
+          <button
+            onClick={() => removeLineItem(index)}
+            className="px-2 py-1 bg-gray-200 rounded text-xs hover:bg-gray-300"
+          >
+            Remove
+          </button>
+        </div>
+      ))}
+
+      <div className="flex gap-2">
+        <button
+          onClick={() => setTerms({ ...terms, lineItems: [...terms.lineItems, emptyLineItem()] })}
+          className="px-2 py-1 bg-gray-200 rounded text-xs hover:bg-gray-300"
+        >
+          Add Line Item
+        </button>
+        <button
+          onClick={() => onSubmit(terms)}
+          disabled={loading || (!itemized && !terms.amount)}
+          className="px-2 py-1 bg-red-600 text-white rounded text-xs hover:bg-red-700 disabled:opacity-50"
+        >
+          {loading ? 'Slashing...' : 'Confirm Slash'}
+        </button>
+        <button
+          onClick={onCancel}
+          className="px-2 py-1 bg-gray-600 text-white rounded text-xs hover:bg-gray-700"
+        >
+          Cancel
+        </button>
+      </div>
+    </div>
+  );
+};
@@ -13,5 +13,4 @@ export * from './pages/EscrowDashboardPage';
 
 // Export utilities if needed elsewhere
 export * from './utils/crypto';
-export * from './utils/pda';
-export * from './utils/schemas'; 
\ No newline at end of file
+export * from './utils/pda';
\ No newline at end of file
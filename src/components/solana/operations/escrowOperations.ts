@@ -1,354 +1,574 @@
-import { PublicKey, Connection, Transaction, TransactionInstruction, SystemProgram } from '@solana/web3.js';
+import {
+  PublicKey,
+  Connection,
+  Transaction,
+  Ed25519Program,
+  SYSVAR_INSTRUCTIONS_PUBKEY
+} from '@solana/web3.js';
 import { WalletContextState } from '@solana/wallet-adapter-react';
-import { createSolanaClient, address, Address } from 'gill';
-import BN from 'bn.js';
-import { LAMPORTS_PER_SOL } from '@solana/web3.js';
+import { Program, BN, Idl } from '@coral-xyz/anchor';
 
+import escrowIdl from '@/lib/escrow-idl.json';
 import { hashString } from '../utils/crypto';
-import { getApartmentEscrowPDA, getStakeRecordPDA } from '../utils/pda';
-import { 
-  InitializeApartmentSchema, 
-  StakeForApartmentSchema, 
-  ResolveStakeSchema, 
-  SlashStakeSchema,
-  StakeRecordSchema 
-} from '../utils/schemas';
-
-const PROGRAM_ID = new PublicKey('Edmq5WTFJL5gtwMmD9HdtJ5N14ivXMP4vprvPxRkFZRJ');
-const PENALTY_WALLET = address('2c8QGXM2tRMh7yb1Zva48ZmQTPMmLZCu159x2hscxxwv');
-
-// Create Solana client
-const solanaClient = createSolanaClient({ urlOrMoniker: 'devnet' });
-const { rpc } = solanaClient;
+import {
+  getApartmentEscrowPDA,
+  getStakeRecordPDA,
+  getSlashDisputePDA,
+  getReputationPDA,
+  getReferralPDA,
+  getReferrerCounterPDA,
+  getProtocolConfigPDA
+} from '../utils/pda';
+import { solToLamports, percentToBps } from '../utils/amounts';
+
+const ARBITER_PANEL = process.env.NEXT_PUBLIC_ESCROW_ARBITER_PANEL;
+
+const SECONDS_PER_DAY = 24 * 60 * 60;
+
+// Terms the lessor creates the escrow with, SOL amounts as entered
+export interface EscrowTerms {
+  requiredStake: string;
+  referralReward: string;
+  referralLimit: number;
+  decisionWindowDays: number;
+  disputeWindowDays: number;
+  slashSplit: { lessorBps: number; penaltyWalletBps: number; referrerBps: number };
+  stakeDiscount: { bpsPerCompletedStake: number; maxDiscountBps: number };
+}
+
+// Suggested terms for a new escrow, the lessor can change all of them
+export const DEFAULT_ESCROW_TERMS: Omit<EscrowTerms, 'requiredStake' | 'referralReward' | 'referralLimit'> = {
+  decisionWindowDays: 7,
+  disputeWindowDays: 3,
+  slashSplit: { lessorBps: 7000, penaltyWalletBps: 3000, referrerBps: 0 },
+  stakeDiscount: { bpsPerCompletedStake: 0, maxDiscountBps: 0 },
+};
+
+// Variant names of the program's SlashReason and DeductionCategory enums
+export const SLASH_REASONS = [
+  'missedViewing',
+  'noShow',
+  'falseInformation',
+  'agreementBreach',
+  'propertyDamage',
+  'other',
+] as const;
+export const DEDUCTION_CATEGORIES = [
+  'damage',
+  'cleaning',
+  'unpaidRent',
+  'utilities',
+  'missedAppointment',
+  'other',
+] as const;
+
+// One itemized deduction of a slash, amount in SOL as entered
+export interface SlashLineItem {
+  category: typeof DEDUCTION_CATEGORIES[number];
+  amount: string;
+  uri: string;
+  evidenceHash: Uint8Array | null;
+}
+
+// What the lessor slashes and why, line items (when given) set the slashed amount to their total
+export interface SlashTerms {
+  amountMode: 'percent' | 'sol';
+  amount: string;
+  reason: typeof SLASH_REASONS[number];
+  lineItems: SlashLineItem[];
+}
+
+// Days as entered, in whole seconds
+const daysToSeconds = (days: number): BN => {
+  if (!Number.isFinite(days) || days <= 0) {
+    throw new Error(`Invalid number of days: ${days}`);
+  }
+  return new BN(Math.round(days * SECONDS_PER_DAY));
+};
+
+// Program client on the app's connection, it only builds transactions and fetches accounts
+const getProgram = (connection: Connection) => new Program(escrowIdl as Idl, { connection });
+
+// Send a transaction signed by the connected wallet
+const sendTransaction = async (
+  transaction: Transaction,
+  wallet: WalletContextState,
+  connection: Connection
+): Promise<string> => {
+  const signature = await wallet.sendTransaction(transaction, connection);
+  await connection.confirmTransaction(signature);
+  return signature;
+};
 
 // Initialize apartment escrow
 export const initializeApartment = async (
   apartmentId: string,
   apartmentOwnerPubkey: string,
-  wallet: WalletContextState
+  terms: EscrowTerms,
+  wallet: WalletContextState,
+  connection: Connection
 ): Promise<string> => {
   if (!wallet.publicKey || !wallet.sendTransaction) {
     throw new Error('Wallet not connected');
   }
+  if (!ARBITER_PANEL) {
+    throw new Error('NEXT_PUBLIC_ESCROW_ARBITER_PANEL is not set');
+  }
 
-  const apartmentHash = Array.from(hashString(apartmentId));
-  const apartmentOwner = address(apartmentOwnerPubkey);
-  const escrowPDA = getApartmentEscrowPDA(apartmentId);
-
-  // Get latest blockhash
-  const { value: latestBlockhash } = await rpc.getLatestBlockhash().send();
-
-  // Create instruction data
-  const buffer = Buffer.alloc(1000);
-  InitializeApartmentSchema.encode({
-    apartment_hash: apartmentHash,
-    apartment_id: apartmentId,
-    apartment_owner: new PublicKey(apartmentOwner)
-  }, buffer);
-
-  const encodedSize = InitializeApartmentSchema.getSpan(buffer);
-  const instructionData = buffer.subarray(0, encodedSize);
-
-  // Create instruction
-  const instruction = new TransactionInstruction({
-    keys: [
-      { pubkey: new PublicKey(escrowPDA), isSigner: false, isWritable: true },
-      { pubkey: wallet.publicKey, isSigner: true, isWritable: true },
-      { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
-    ],
-    data: Buffer.concat([
-      Buffer.from([163, 134, 140, 192, 15, 6, 227, 23]), // initialize_apartment discriminator
-      instructionData
-    ]),
-    programId: PROGRAM_ID,
-  });
-
-  // Create and send transaction
-  const transaction = new Transaction();
-  transaction.add(instruction);
-  transaction.recentBlockhash = latestBlockhash.blockhash;
-  transaction.feePayer = wallet.publicKey;
-
-  const connection = new Connection('https://api.devnet.solana.com');
-  const signature = await wallet.sendTransaction(transaction, connection);
-  
-  return signature;
+  const program = getProgram(connection);
+
+  const transaction = await program.methods
+    .initializeApartment(
+      Array.from(hashString(apartmentId)),
+      apartmentId,
+      new PublicKey(apartmentOwnerPubkey),
+      daysToSeconds(terms.decisionWindowDays),
+      solToLamports(terms.requiredStake),
+      solToLamports(terms.referralReward),
+      terms.referralLimit,
+      daysToSeconds(terms.disputeWindowDays),
+      terms.slashSplit,
+      terms.stakeDiscount
+    )
+    .accountsPartial({
+      escrowAccount: new PublicKey(getApartmentEscrowPDA(apartmentId)),
+      arbiterPanel: new PublicKey(ARBITER_PANEL),
+      initializer: wallet.publicKey,
+      stakeMint: null,
+      stakeVault: null,
+      tokenProgram: null,
+      associatedTokenProgram: null,
+    })
+    .transaction();
+
+  return sendTransaction(transaction, wallet, connection);
 };
 
 // Stake SOL for apartment
 export const stakeForApartment = async (
   apartmentId: string,
-  amount: number,
+  amount: string,
   profileId: string,
+  wallet: WalletContextState,
+  connection: Connection
+): Promise<string> => {
+  if (!wallet.publicKey || !wallet.sendTransaction) {
+    throw new Error('Wallet not connected');
+  }
+
+  const program = getProgram(connection);
+
+  // Reputation the staker earned on this profile lowers the required stake, once it exists
+  const reputationPDA = new PublicKey(getReputationPDA(profileId, wallet.publicKey));
+  const reputation = await (program.account as any).reputation.fetchNullable(reputationPDA);
+
+  const transaction = await program.methods
+    .stakeForApartment(
+      Array.from(hashString(apartmentId)),
+      solToLamports(amount),
+      Array.from(hashString(profileId)),
+      apartmentId,
+      profileId
+    )
+    .accountsPartial({
+      escrowAccount: new PublicKey(getApartmentEscrowPDA(apartmentId)),
+      stakeRecord: new PublicKey(getStakeRecordPDA(apartmentId, profileId)),
+      reputation: reputation ? reputationPDA : null,
+      staker: wallet.publicKey,
+      stakeMint: null,
+      stakeVault: null,
+      stakerTokenAccount: null,
+      tokenProgram: null,
+    })
+    .transaction();
+
+  return sendTransaction(transaction, wallet, connection);
+};
+
+// Consent message the tenant signs, must match referral_consent_message in the program
+const referralConsentMessage = (referrer: PublicKey, tenantProfileId: string, apartmentId: string): Uint8Array =>
+  new TextEncoder().encode(
+    `Referral consent: ${referrer.toBase58()} referred ${tenantProfileId} for apartment ${apartmentId}`
+  );
+
+// Sign a referral consent as the tenant (the referrer submits it with submitReferral, it never expires)
+export const signReferral = async (
+  apartmentId: string,
+  tenantProfileId: string,
+  referrerPubkey: string,
   wallet: WalletContextState
+): Promise<string> => {
+  if (!wallet.publicKey || !wallet.signMessage) {
+    throw new Error('Wallet does not support message signing');
+  }
+
+  const message = referralConsentMessage(new PublicKey(referrerPubkey), tenantProfileId, apartmentId);
+  const signature = await wallet.signMessage(message);
+
+  const consent = {
+    tenant: wallet.publicKey.toBase58(),
+    tenantProfileId,
+    signature: Buffer.from(signature).toString('base64'),
+  };
+  return Buffer.from(JSON.stringify(consent)).toString('base64');
+};
+
+// Submit a referral with the consent the tenant signed with signReferral (referrer action)
+export const submitReferral = async (
+  apartmentId: string,
+  signedReferral: string,
+  wallet: WalletContextState,
+  connection: Connection
 ): Promise<string> => {
   if (!wallet.publicKey || !wallet.sendTransaction) {
     throw new Error('Wallet not connected');
   }
 
-  const amountLamports = amount * LAMPORTS_PER_SOL;
-  const escrowPDA = getApartmentEscrowPDA(apartmentId);
-  const stakeRecordPDA = getStakeRecordPDA(apartmentId, profileId);
-
-  const { value: latestBlockhash } = await rpc.getLatestBlockhash().send();
-
-  // Create instruction data
-  const buffer = Buffer.alloc(1000);
-  StakeForApartmentSchema.encode({
-    apartment_hash: Array.from(hashString(apartmentId)),
-    amount: new BN(amountLamports),
-    profile_hash: Array.from(hashString(profileId)),
-    apartment_id: apartmentId,
-    tenant_profile_id: profileId
-  }, buffer);
-
-  const encodedSize = StakeForApartmentSchema.getSpan(buffer);
-  const instructionData = buffer.subarray(0, encodedSize);
-
-  // Create instruction
-  const instruction = new TransactionInstruction({
-    keys: [
-      { pubkey: new PublicKey(escrowPDA), isSigner: false, isWritable: true },
-      { pubkey: new PublicKey(stakeRecordPDA), isSigner: false, isWritable: true },
-      { pubkey: wallet.publicKey, isSigner: true, isWritable: true },
-      { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
-    ],
-    data: Buffer.concat([
-      Buffer.from([254, 32, 189, 253, 3, 2, 123, 132]), // stake_for_apartment discriminator
-      instructionData
-    ]),
-    programId: PROGRAM_ID,
+  const consent = JSON.parse(Buffer.from(signedReferral.trim(), 'base64').toString());
+  const tenant = new PublicKey(consent.tenant);
+  const tenantProfileId: string = consent.tenantProfileId;
+  const referrer = wallet.publicKey;
+
+  // The program checks the tenant's signature through this ed25519 instruction right before its own
+  const consentInstruction = Ed25519Program.createInstructionWithPublicKey({
+    publicKey: tenant.toBytes(),
+    message: referralConsentMessage(referrer, tenantProfileId, apartmentId),
+    signature: Buffer.from(consent.signature, 'base64'),
   });
 
-  // Create and send transaction
-  const transaction = new Transaction();
-  transaction.add(instruction);
-  transaction.recentBlockhash = latestBlockhash.blockhash;
-  transaction.feePayer = wallet.publicKey;
+  const program = getProgram(connection);
+
+  const transaction = await program.methods
+    .registerReferral(
+      Array.from(hashString(apartmentId)),
+      Array.from(hashString(tenantProfileId)),
+      apartmentId,
+      tenantProfileId
+    )
+    .accountsPartial({
+      escrowAccount: new PublicKey(getApartmentEscrowPDA(apartmentId)),
+      referral: new PublicKey(getReferralPDA(apartmentId, tenantProfileId)),
+      referrerCounter: new PublicKey(getReferrerCounterPDA(apartmentId, referrer)),
+      stakeRecord: new PublicKey(getStakeRecordPDA(apartmentId, tenantProfileId)),
+      referrer,
+      tenant,
+      instructions: SYSVAR_INSTRUCTIONS_PUBKEY,
+    })
+    .preInstructions([consentInstruction])
+    .transaction();
+
+  return sendTransaction(transaction, wallet, connection);
+};
 
-  const connection = new Connection('https://api.devnet.solana.com');
-  const signature = await wallet.sendTransaction(transaction, connection);
-  
-  return signature;
+// Withdraw stake (staker takes their stake back before they are approved)
+export const withdrawStake = async (
+  apartmentId: string,
+  stakeRecord: any,
+  wallet: WalletContextState,
+  connection: Connection
+): Promise<string> => {
+  if (!wallet.publicKey || !wallet.sendTransaction) {
+    throw new Error('Wallet not connected');
+  }
+
+  const program = getProgram(connection);
+  const tenantProfileId = stakeRecord.tenantProfileId;
+
+  const transaction = await program.methods
+    .withdrawStake(
+      Array.from(hashString(apartmentId)),
+      Array.from(hashString(tenantProfileId)),
+      apartmentId,
+      tenantProfileId
+    )
+    .accountsPartial({
+      escrowAccount: new PublicKey(getApartmentEscrowPDA(apartmentId)),
+      stakeRecord: new PublicKey(getStakeRecordPDA(apartmentId, tenantProfileId)),
+      staker: wallet.publicKey,
+      stakeMint: null,
+      stakeVault: null,
+      stakerTokenAccount: null,
+      tokenProgram: null,
+    })
+    .transaction();
+
+  return sendTransaction(transaction, wallet, connection);
 };
 
-// Resolve stake (return money to tenant)
-export const resolveStake = async (
+// Approve tenant (lessor picks the stake that gets the lease)
+export const approveTenant = async (
   apartmentId: string,
   stakeRecord: any,
-  apartmentOwnerPubkey: string,
-  referrerPubkey: string | null,
-  rewardAmount: number,
-  wallet: WalletContextState
+  wallet: WalletContextState,
+  connection: Connection
 ): Promise<string> => {
   if (!wallet.publicKey || !wallet.sendTransaction) {
     throw new Error('Wallet not connected');
   }
 
-  console.log('=== RESOLVE STAKE DEBUG ===');
-  console.log('apartmentId:', apartmentId);
-  console.log('stakeRecord:', stakeRecord);
-  console.log('apartmentOwnerPubkey:', apartmentOwnerPubkey);
-  console.log('referrerPubkey:', referrerPubkey);
-  console.log('rewardAmount:', rewardAmount);
-  console.log('wallet.publicKey:', wallet.publicKey.toBase58());
-  console.log('Connected wallet === apartment owner?', wallet.publicKey.toBase58() === apartmentOwnerPubkey);
-
-  const tenantProfileId = stakeRecord.tenant_profile_id;
-  const apartmentHash = Array.from(hashString(apartmentId));
-  const profileHash = Array.from(hashString(tenantProfileId));
-  
-  const apartmentOwner = new PublicKey(apartmentOwnerPubkey);
-  const escrowPDA = getApartmentEscrowPDA(apartmentId);
-  const stakeRecordPDA = getStakeRecordPDA(apartmentId, tenantProfileId);
-
-  console.log('PDAs:');
-  console.log('escrowPDA:', escrowPDA);
-  console.log('stakeRecordPDA:', stakeRecordPDA);
-  console.log('stakeRecord.staker:', stakeRecord.staker);
-
-  let referrerPublicKey: PublicKey | null = null;
-  if (referrerPubkey) {
-    referrerPublicKey = new PublicKey(referrerPubkey);
+  const program = getProgram(connection);
+  const tenantProfileId = stakeRecord.tenantProfileId;
+
+  const transaction = await program.methods
+    .approveTenant(
+      Array.from(hashString(apartmentId)),
+      Array.from(hashString(tenantProfileId)),
+      apartmentId,
+      tenantProfileId
+    )
+    .accountsPartial({
+      escrowAccount: new PublicKey(getApartmentEscrowPDA(apartmentId)),
+      stakeRecord: new PublicKey(getStakeRecordPDA(apartmentId, tenantProfileId)),
+      lessor: wallet.publicKey,
+    })
+    .transaction();
+
+  return sendTransaction(transaction, wallet, connection);
+};
+
+// Start lease (lessor action once the approved tenant signed the lease)
+export const startLease = async (
+  apartmentId: string,
+  wallet: WalletContextState,
+  connection: Connection
+): Promise<string> => {
+  if (!wallet.publicKey || !wallet.sendTransaction) {
+    throw new Error('Wallet not connected');
   }
 
-  console.log('Referrer logic:');
-  console.log('Has referrerPubkey?', !!referrerPubkey);
-  console.log('Final referrer will be:', (referrerPublicKey || apartmentOwner).toBase58());
-  console.log('apartmentOwner pubkey:', apartmentOwner.toBase58());
-
-  // Create instruction data
-  const buffer = Buffer.alloc(1000);
-  const instructionData = {
-    apartmentHash,
-    profileHash,
-    apartmentId,
-    tenantProfileId,
-    apartmentOwner,
-    referrerPubkey: referrerPublicKey,
-    rewardAmount: new BN(rewardAmount * LAMPORTS_PER_SOL),
-  };
-  
-  console.log('Instruction data:', instructionData);
-  
-  ResolveStakeSchema.encode(instructionData, buffer);
-
-  const encodedSize = ResolveStakeSchema.getSpan(buffer);
-  const instructionDataBuffer = buffer.subarray(0, encodedSize);
-
-  // Create accounts array - always include all accounts in IDL order
-  const accounts = [
-    { pubkey: new PublicKey(escrowPDA), isSigner: false, isWritable: true },
-    { pubkey: new PublicKey(stakeRecordPDA), isSigner: false, isWritable: true },
-    { pubkey: wallet.publicKey, isSigner: true, isWritable: false },
-    { pubkey: new PublicKey(stakeRecord.staker), isSigner: false, isWritable: true },
-    { 
-      pubkey: referrerPublicKey || apartmentOwner, // Use apartment owner as referrer if no actual referrer
-      isSigner: false, 
-      isWritable: true 
-    },
-  ];
-
-  console.log('Accounts:', accounts.map(acc => ({
-    pubkey: acc.pubkey.toBase58(),
-    isSigner: acc.isSigner,
-    isWritable: acc.isWritable
-  })));
-
-  // Create instruction
-  const instruction = new TransactionInstruction({
-    keys: accounts,
-    programId: PROGRAM_ID,
-    data: Buffer.concat([
-      Buffer.from([162, 136, 9, 179, 86, 213, 52, 160]), // resolve_stake discriminator
-      instructionDataBuffer
-    ]),
-  });
+  const program = getProgram(connection);
 
-  console.log('Instruction created:', {
-    programId: instruction.programId.toBase58(),
-    dataLength: instruction.data.length,
-    keysLength: instruction.keys.length
-  });
+  const transaction = await program.methods
+    .startLease(Array.from(hashString(apartmentId)), apartmentId)
+    .accountsPartial({
+      escrowAccount: new PublicKey(getApartmentEscrowPDA(apartmentId)),
+      lessor: wallet.publicKey,
+    })
+    .transaction();
 
-  // Create and send transaction
-  const transaction = new Transaction().add(instruction);
-  const connection = new Connection('https://api.devnet.solana.com');
-  
-  console.log('Sending transaction...');
-  const signature = await wallet.sendTransaction(transaction, connection);
-  console.log('Transaction sent, confirming...');
-  await connection.confirmTransaction(signature);
+  return sendTransaction(transaction, wallet, connection);
+};
 
-  return signature;
+// Look up the referrer the tenant registered on-chain for this apartment, if any
+const fetchReferrer = async (
+  program: Program,
+  apartmentId: string,
+  tenantProfileId: string,
+  staker: PublicKey
+): Promise<PublicKey | null> => {
+  const referral: any = await (program.account as any).referral.fetchNullable(
+    new PublicKey(getReferralPDA(apartmentId, tenantProfileId))
+  );
+  return referral && referral.tenant.equals(staker) ? referral.referrer : null;
+};
+
+// Resolve stake (return money to tenant, paying the referrer the tenant registered on-chain)
+export const resolveStake = async (
+  apartmentId: string,
+  stakeRecord: any,
+  apartmentOwnerPubkey: string,
+  wallet: WalletContextState,
+  connection: Connection
+): Promise<string> => {
+  if (!wallet.publicKey || !wallet.sendTransaction) {
+    throw new Error('Wallet not connected');
+  }
+
+  const program = getProgram(connection);
+  const tenantProfileId = stakeRecord.tenantProfileId;
+  const staker = new PublicKey(stakeRecord.staker);
+  const referrer = await fetchReferrer(program, apartmentId, tenantProfileId, staker);
+  const escrow = await (program.account as any).apartmentEscrow.fetch(
+    new PublicKey(getApartmentEscrowPDA(apartmentId))
+  );
+  // Only the approved tenant's reputation is recorded, other applicants get a plain refund
+  const approvedTenant = !!escrow.approvedProfile
+    && Buffer.from(escrow.approvedProfile).equals(hashString(tenantProfileId));
+
+  const transaction = await program.methods
+    .resolveStake(
+      Array.from(hashString(apartmentId)),
+      Array.from(hashString(tenantProfileId)),
+      apartmentId,
+      tenantProfileId,
+      new PublicKey(apartmentOwnerPubkey),
+      []
+    )
+    .accountsPartial({
+      escrowAccount: new PublicKey(getApartmentEscrowPDA(apartmentId)),
+      stakeRecord: new PublicKey(getStakeRecordPDA(apartmentId, tenantProfileId)),
+      reputation: approvedTenant ? new PublicKey(getReputationPDA(tenantProfileId, staker)) : null,
+      lessor: wallet.publicKey,
+      staker,
+      referrer,
+      referrerCounter: referrer ? new PublicKey(getReferrerCounterPDA(apartmentId, referrer)) : null,
+      stakeMint: null,
+      stakeVault: null,
+      stakerTokenAccount: null,
+      referrerTokenAccount: null,
+      treasuryTokenAccount: null,
+      tokenProgram: null,
+    })
+    .transaction();
+
+  return sendTransaction(transaction, wallet, connection);
 };
 
-// Slash stake (send money to penalty wallet)
+// Slash stake (opens a dispute window, the penalty is paid out when the slash is finalized)
 export const slashStake = async (
   apartmentId: string,
   stakeRecord: any,
   apartmentOwnerPubkey: string,
-  wallet: WalletContextState
+  terms: SlashTerms,
+  wallet: WalletContextState,
+  connection: Connection
 ): Promise<string> => {
   if (!wallet.publicKey || !wallet.sendTransaction) {
     throw new Error('Wallet not connected');
   }
 
-  const tenantProfileId = stakeRecord.tenant_profile_id;
-  const apartmentHash = Array.from(hashString(apartmentId));
-  const profileHash = Array.from(hashString(tenantProfileId));
-  
-  const apartmentOwner = new PublicKey(apartmentOwnerPubkey);
-  const escrowPDA = getApartmentEscrowPDA(apartmentId);
-  const stakeRecordPDA = getStakeRecordPDA(apartmentId, tenantProfileId);
-
-  // Create instruction data
-  const buffer = Buffer.alloc(1000);
-  SlashStakeSchema.encode({
-    apartmentHash,
-    profileHash,
-    apartmentId,
-    tenantProfileId,
-    apartmentOwner,
-  }, buffer);
-
-  const encodedSize = SlashStakeSchema.getSpan(buffer);
-  const instructionData = buffer.subarray(0, encodedSize);
-
-  // Create instruction
-  const instruction = new TransactionInstruction({
-    keys: [
-      { pubkey: new PublicKey(escrowPDA), isSigner: false, isWritable: true },
-      { pubkey: new PublicKey(stakeRecordPDA), isSigner: false, isWritable: true },
-      { pubkey: wallet.publicKey, isSigner: true, isWritable: false },
-      { pubkey: new PublicKey(PENALTY_WALLET), isSigner: false, isWritable: true },
-    ],
-    programId: PROGRAM_ID,
-    data: Buffer.concat([
-      Buffer.from([190, 242, 137, 27, 41, 18, 233, 37]), // slash_stake discriminator
-      instructionData
-    ]),
-  });
+  const program = getProgram(connection);
+  const tenantProfileId = stakeRecord.tenantProfileId;
+  const staker = new PublicKey(stakeRecord.staker);
+
+  const lineItems = terms.lineItems.map((item) => ({
+    category: { [item.category]: {} },
+    amount: solToLamports(item.amount),
+    evidenceHash: Array.from(item.evidenceHash ?? new Uint8Array(32)),
+    uri: item.uri,
+  }));
+  // The program requires line items to add up to the slashed amount, so they set it
+  const slashAmount = lineItems.length > 0
+    ? { amount: [lineItems.reduce((total, item) => total.add(item.amount), new BN(0))] }
+    : terms.amountMode === 'percent'
+      ? { basisPoints: [percentToBps(terms.amount)] }
+      : { amount: [solToLamports(terms.amount)] };
+
+  const transaction = await program.methods
+    .slashStake(
+      Array.from(hashString(apartmentId)),
+      Array.from(hashString(tenantProfileId)),
+      apartmentId,
+      tenantProfileId,
+      new PublicKey(apartmentOwnerPubkey),
+      slashAmount,
+      { [terms.reason]: {} },
+      lineItems
+    )
+    .accountsPartial({
+      escrowAccount: new PublicKey(getApartmentEscrowPDA(apartmentId)),
+      stakeRecord: new PublicKey(getStakeRecordPDA(apartmentId, tenantProfileId)),
+      reputation: new PublicKey(getReputationPDA(tenantProfileId, staker)),
+      lessor: wallet.publicKey,
+      staker,
+      stakeMint: null,
+      stakeVault: null,
+      stakerTokenAccount: null,
+      tokenProgram: null,
+    })
+    .transaction();
+
+  return sendTransaction(transaction, wallet, connection);
+};
 
-  // Create and send transaction
-  const transaction = new Transaction().add(instruction);
-  const connection = new Connection('https://api.devnet.solana.com');
-  
-  const signature = await wallet.sendTransaction(transaction, connection);
-  await connection.confirmTransaction(signature);
+// Finalize slash (anyone, once the dispute window closed without a dispute)
+export const finalizeSlash = async (
+  apartmentId: string,
+  slashDispute: any,
+  lessorPubkey: string,
+  wallet: WalletContextState,
+  connection: Connection
+): Promise<string> => {
+  if (!wallet.publicKey || !wallet.sendTransaction) {
+    throw new Error('Wallet not connected');
+  }
 
-  return signature;
+  const program = getProgram(connection);
+  const tenantProfileId = slashDispute.tenantProfileId;
+  const referrer = await fetchReferrer(program, apartmentId, tenantProfileId, slashDispute.staker);
+  const config = await (program.account as any).protocolConfig.fetch(new PublicKey(getProtocolConfigPDA()));
+
+  const transaction = await program.methods
+    .finalizeSlash(Array.from(hashString(apartmentId)), Array.from(hashString(tenantProfileId)))
+    .accountsPartial({
+      escrowAccount: new PublicKey(getApartmentEscrowPDA(apartmentId)),
+      slashDispute: slashDispute.address,
+      reputation: new PublicKey(getReputationPDA(tenantProfileId, slashDispute.staker)),
+      penaltyWallet: config.penaltyWallet,
+      lessor: new PublicKey(lessorPubkey),
+      referrer,
+      rentPayer: slashDispute.rentPayer,
+      caller: wallet.publicKey,
+      stakeMint: null,
+      stakeVault: null,
+      lessorTokenAccount: null,
+      penaltyWalletTokenAccount: null,
+      referrerTokenAccount: null,
+      tokenProgram: null,
+    })
+    .transaction();
+
+  return sendTransaction(transaction, wallet, connection);
+};
+
+// Fetch slashes waiting for their dispute window or ruling, one per slashed stake record
+export const fetchSlashDisputes = async (
+  apartmentId: string,
+  stakeRecords: any[],
+  connection: Connection
+): Promise<any[]> => {
+  try {
+    const addresses = stakeRecords.map((record) =>
+      new PublicKey(getSlashDisputePDA(apartmentId, record.tenantProfileId))
+    );
+    const disputes = await (getProgram(connection).account as any).slashDispute.fetchMultiple(addresses);
+
+    return disputes
+      .map((dispute: any, index: number) => dispute && {
+        ...dispute,
+        status: Object.keys(dispute.status)[0],
+        address: addresses[index]
+      })
+      .filter(Boolean);
+  } catch (error) {
+    console.error('Error fetching slash disputes:', error);
+    return [];
+  }
 };
 
 // Fetch stake records for an apartment
-export const fetchStakeRecords = async (apartmentId: string): Promise<any[]> => {
+export const fetchStakeRecords = async (apartmentId: string, connection: Connection): Promise<any[]> => {
   try {
-    const programAccounts = await rpc.getProgramAccounts(address(PROGRAM_ID.toBase58() as Address), {
-      encoding: 'base64'
-    }).send();
-
-    const stakeRecords = [];
-    
-    for (const accountInfo of programAccounts) {
-      try {
-        const buffer = Buffer.from(accountInfo.account.data[0], 'base64');
-        
-        // Check if this account has the right discriminator for StakeRecord
-        const expectedDiscriminator = [174, 163, 11, 208, 150, 236, 11, 205];
-        const actualDiscriminator = Array.from(buffer.slice(0, 8));
-        
-        if (JSON.stringify(actualDiscriminator) === JSON.stringify(expectedDiscriminator)) {
-          const accountData = buffer.slice(8);
-          const decoded = StakeRecordSchema.decode(accountData);
-          
-          // Only include stakes for this apartment
-          if (decoded.apartment_id === apartmentId) {
-            stakeRecords.push({
-              ...decoded,
-              amount: decoded.amount.toString(),
-              address: accountInfo.pubkey
-            });
-          }
-        }
-      } catch (e) {
-        continue;
-      }
-    }
-    
-    return stakeRecords;
+    const stakeRecords = await (getProgram(connection).account as any).stakeRecord.all();
+
+    return stakeRecords
+      .filter(({ account }: any) => account.apartmentId === apartmentId)
+      .map(({ publicKey, account }: any) => ({
+        ...account,
+        status: Object.keys(account.status)[0],
+        address: publicKey
+      }));
   } catch (error) {
     console.error('Error fetching stake records:', error);
     return [];
   }
 };
 
-// Check if escrow exists
-export const checkEscrowExists = async (apartmentId: string): Promise<boolean> => {
+// Fetch escrow state, null if the escrow is not initialized
+export const fetchEscrow = async (apartmentId: string, connection: Connection): Promise<any | null> => {
   try {
-    const escrowPDA = getApartmentEscrowPDA(apartmentId);
-    const { value: escrowAccount } = await rpc.getAccountInfo(escrowPDA, { encoding: 'base64' }).send();
-    return escrowAccount !== null;
+    const escrow = await (getProgram(connection).account as any).apartmentEscrow.fetchNullable(
+      new PublicKey(getApartmentEscrowPDA(apartmentId))
+    );
+    if (!escrow) return null;
+
+    return {
+      ...escrow,
+      status: Object.keys(escrow.status)[0],
+      approvedProfile: escrow.approvedProfile ? Buffer.from(escrow.approvedProfile).toString('hex') : null
+    };
   } catch (error) {
-    return false;
+    console.error('Error fetching escrow:', error);
+    return null;
   }
-}; 
\ No newline at end of file
+};
@@ -1,7 +1,11 @@
-import React from 'react';
+import React, { useState } from 'react';
+import { useWallet } from '@solana/wallet-adapter-react';
 import { WalletMultiButton } from '@solana/wallet-adapter-react-ui';
 import { LAMPORTS_PER_SOL } from '@solana/web3.js';
 import { Apartment, Profile } from '@/lib/schema';
+import { hashString } from '../utils/crypto';
+import { SlashTerms } from '../operations/escrowOperations';
+import { SlashStakeForm } from '../SlashStakeForm';
 
 interface EscrowDashboardPageProps {
   apartmentId: string;
@@ -11,15 +15,24 @@ interface EscrowDashboardPageProps {
   profile: Profile | null;
   escrowData: any;
   stakeRecords: any[];
+  slashDisputes: any[];
   stakeAmount: string;
   setStakeAmount: (amount: string) => void;
   loading: boolean;
   isOwner: boolean;
   referrerPubkey: string | null;
+  isReferrer: boolean;
+  signedReferral: string | null;
   onRefresh: () => void;
   onStake: () => void;
+  onSignReferral: () => void;
+  onSubmitReferral: (signedReferral: string) => void;
+  onWithdraw: (record: any) => void;
+  onApprove: (record: any) => void;
+  onStartLease: () => void;
   onResolve: (record: any) => void;
-  onSlash: (record: any) => void;
+  onSlash: (record: any, terms: SlashTerms) => void;
+  onFinalizeSlash: (slashDispute: any) => void;
 }
 
 export const EscrowDashboardPage: React.FC<EscrowDashboardPageProps> = ({
@@ -30,16 +43,37 @@ export const EscrowDashboardPage: React.FC<EscrowDashboardPageProps> = ({
   profile,
   escrowData,
   stakeRecords,
+  slashDisputes,
   stakeAmount,
   setStakeAmount,
   loading,
   isOwner,
   referrerPubkey,
+  isReferrer,
+  signedReferral,
   onRefresh,
   onStake,
+  onSignReferral,
+  onSubmitReferral,
+  onWithdraw,
+  onApprove,
+  onStartLease,
   onResolve,
-  onSlash
+  onSlash,
+  onFinalizeSlash
 }) => {
+  const { publicKey } = useWallet();
+  const [referralInput, setReferralInput] = useState('');
+  const [slashingRecord, setSlashingRecord] = useState<string | null>(null);
+
+  // The stake of the tenant the owner approved on-chain
+  const isApprovedStake = (record: any) =>
+    escrowData?.approvedProfile === hashString(record.tenantProfileId).toString('hex');
+  const leaseOpen = escrowData?.status === 'tenantApproved' || escrowData?.status === 'leaseActive';
+  // Referrals must be on-chain before the tenant stakes
+  const canRegisterReferral = Boolean(referrerPubkey) && escrowData?.status === 'open' &&
+    !stakeRecords.some((record) => record.tenantProfileId === apartment?.approved_profile);
+
   return (
     <div className="min-h-screen bg-gray-50 p-8">
       <div className="max-w-4xl mx-auto">
@@ -100,11 +134,20 @@ export const EscrowDashboardPage: React.FC<EscrowDashboardPageProps> = ({
           <div className="mb-6 p-4 bg-green-50 border border-green-200 rounded-lg">
             <h3 className="text-green-700 font-medium mb-2">✅ Escrow Active</h3>
             <div className="grid grid-cols-2 gap-4 text-sm">
-              <p><strong>Total Staked:</strong> {escrowData.totalStaked ? (escrowData.totalStaked.toNumber() / LAMPORTS_PER_SOL).toFixed(4) : '0'} SOL</p>
+              <p><strong>Total Staked:</strong> {(escrowData.totalStaked.toNumber() / LAMPORTS_PER_SOL).toFixed(4)} SOL</p>
               <p><strong>Owner:</strong> {escrowData.lessor?.toString().slice(0, 8)}...</p>
-              <p><strong>Active Stakes:</strong> {stakeRecords.length}</p>
-              <p><strong>Status:</strong> {escrowData.isActive ? 'Active' : 'Inactive'}</p>
+              <p><strong>Active Stakes:</strong> {escrowData.activeStakes}</p>
+              <p><strong>Status:</strong> {escrowData.status}</p>
             </div>
+            {isOwner && escrowData.status === 'tenantApproved' && (
+              <button
+                onClick={onStartLease}
+                disabled={loading}
+                className="mt-3 px-4 py-2 bg-green-600 text-white rounded-lg hover:bg-green-700 disabled:opacity-50"
+              >
+                {loading ? 'Starting...' : 'Start Lease'}
+              </button>
+            )}
           </div>
         )}
 
@@ -140,6 +183,54 @@ export const EscrowDashboardPage: React.FC<EscrowDashboardPageProps> = ({
             </div>
           )}
 
+          {/* Referral */}
+          {canRegisterReferral && (
+            <div className="bg-white rounded-lg shadow p-6">
+              <h3 className="text-lg font-semibold mb-4">Referral</h3>
+              {isReferrer ? (
+                <div className="space-y-4">
+                  <textarea
+                    value={referralInput}
+                    onChange={(e) => setReferralInput(e.target.value)}
+                    placeholder="Paste the referral consent the tenant signed"
+                    className="w-full px-3 py-2 border rounded-lg font-mono text-xs"
+                    rows={4}
+                  />
+                  <button
+                    onClick={() => onSubmitReferral(referralInput)}
+                    disabled={loading || !referralInput}
+                    className="px-4 py-2 bg-purple-600 text-white rounded-lg hover:bg-purple-700 disabled:opacity-50"
+                  >
+                    {loading ? 'Submitting...' : 'Submit Referral'}
+                  </button>
+                </div>
+              ) : apartment?.approved_profile === profile?.id && (
+                <div className="space-y-4">
+                  <button
+                    onClick={onSignReferral}
+                    disabled={loading}
+                    className="px-4 py-2 bg-purple-600 text-white rounded-lg hover:bg-purple-700 disabled:opacity-50"
+                  >
+                    {loading ? 'Signing...' : 'Sign Referral'}
+                  </button>
+                  {signedReferral && (
+                    <>
+                      <textarea
+                        readOnly
+                        value={signedReferral}
+                        className="w-full px-3 py-2 border rounded-lg font-mono text-xs"
+                        rows={4}
+                      />
+                      <p className="text-xs text-gray-500">
+                        Send this to your referrer, they can submit it any time before you stake.
+                      </p>
+                    </>
+                  )}
+                </div>
+              )}
+            </div>
+          )}
+
           {/* Stakes List */}
           <div className="bg-white rounded-lg shadow p-6">
             <h3 className="text-lg font-semibold mb-4">Active Stakes</h3>
@@ -151,15 +242,33 @@ export const EscrowDashboardPage: React.FC<EscrowDashboardPageProps> = ({
                   <div key={index} className="border rounded-lg p-3">
                     <div className="flex justify-between items-start">
                       <div className="flex-1">
-                        <p className="font-medium text-sm">Profile: {record.tenant_profile_id}</p>
+                        <p className="font-medium text-sm">Profile: {record.tenantProfileId}</p>
                         <p className="text-sm text-gray-600">
-                          Amount: {(parseFloat(record.amount) / LAMPORTS_PER_SOL).toFixed(4)} SOL
+                          Amount: {(record.amount.toNumber() / LAMPORTS_PER_SOL).toFixed(4)} SOL
                         </p>
                         <p className="text-xs text-gray-500">
                           Staker: {record.staker.toString().slice(0, 8)}...
                         </p>
                       </div>
-                      {isOwner && record.is_active && (
+                      {record.status === 'active' && !isApprovedStake(record) && publicKey?.equals(record.staker) && (
+                        <button
+                          onClick={() => onWithdraw(record)}
+                          disabled={loading}
+                          className="px-2 py-1 bg-gray-600 text-white rounded text-xs hover:bg-gray-700"
+                        >
+                          Withdraw
+                        </button>
+                      )}
+                      {isOwner && record.status === 'active' && escrowData?.status === 'open' && (
+                        <button
+                          onClick={() => onApprove(record)}
+                          disabled={loading}
+                          className="px-2 py-1 bg-blue-600 text-white rounded text-xs hover:bg-blue-700"
+                        >
+                          Approve
+                        </button>
+                      )}
+                      {isOwner && record.status === 'active' && leaseOpen && isApprovedStake(record) && (
                         <div className="flex gap-1">
                           <button
                             onClick={() => onResolve(record)}
@@ -169,7 +278,7 @@ export const EscrowDashboardPage: React.FC<EscrowDashboardPageProps> = ({
                             Resolve
                           </button>
                           <button
-                            onClick={() => onSlash(record)}
+                            onClick={() => setSlashingRecord(String(record.address))}
                             disabled={loading}
                             className="px-2 py-1 bg-red-600 text-white rounded text-xs hover:bg-red-700"
                           >
@@ -178,6 +287,16 @@ export const EscrowDashboardPage: React.FC<EscrowDashboardPageProps> = ({
                         </div>
                       )}
                     </div>
+                    {slashingRecord === String(record.address) && (
+                      <SlashStakeForm
+                        loading={loading}
+                        onSubmit={(terms) => {
+                          setSlashingRecord(null);
+                          onSlash(record, terms);
+                        }}
+                        onCancel={() => setSlashingRecord(null)}
+                      />
+                    )}
                   </div>
                 ))}
               </div>
@@ -185,6 +304,37 @@ export const EscrowDashboardPage: React.FC<EscrowDashboardPageProps> = ({
           </div>
         </div>
 
+        {/* Slashes */}
+        {slashDisputes.length > 0 && (
+          <div className="mt-6 bg-white rounded-lg shadow p-6">
+            <h3 className="text-lg font-semibold mb-4">Pending Slashes</h3>
+            <div className="space-y-3">
+              {slashDisputes.map((dispute) => (
+                <div key={dispute.address.toBase58()} className="border rounded-lg p-3 flex justify-between items-start">
+                  <div className="flex-1">
+                    <p className="font-medium text-sm">Profile: {dispute.tenantProfileId}</p>
+                    <p className="text-sm text-gray-600">
+                      Amount: {(dispute.amount.toNumber() / LAMPORTS_PER_SOL).toFixed(4)} SOL • {dispute.status}
+                    </p>
+                    <p className="text-xs text-gray-500">
+                      Dispute window ends: {new Date(dispute.disputeDeadline.toNumber() * 1000).toLocaleString()}
+                    </p>
+                  </div>
+                  {dispute.status === 'pending' && Date.now() / 1000 > dispute.disputeDeadline.toNumber() && (
+                    <button
+                      onClick={() => onFinalizeSlash(dispute)}
+                      disabled={loading}
+                      className="px-2 py-1 bg-red-600 text-white rounded text-xs hover:bg-red-700"
+                    >
+                      Finalize
+                    </button>
+                  )}
+                </div>
+              ))}
+            </div>
+          </div>
+        )}
+
         {/* Instructions */}
         <div className="mt-6 bg-blue-50 border border-blue-200 rounded-lg p-4">
           <h3 className="font-semibold text-blue-900 mb-2">How it works:</h3>
@@ -192,7 +342,7 @@ export const EscrowDashboardPage: React.FC<EscrowDashboardPageProps> = ({
             <li><strong>Initialize:</strong> Apartment owner sets up escrow with their public key</li>
             <li><strong>Access Control:</strong> Only owner or approved tenant (from database) can access</li>
             <li><strong>Stake:</strong> Approved users can deposit SOL mapped to their profile ID</li>
-            <li><strong>Resolve/Slash:</strong> Only apartment owner can return money or send to penalty wallet</li>
+            <li><strong>Resolve/Slash:</strong> Only apartment owner can return money or slash it, with the amount, reason and line items entered by the owner and the split set in the escrow terms</li>
           </ol>
           {apartmentOwnerProfile && (
             <div className="mt-3 p-3 bg-yellow-100 rounded text-sm text-yellow-800">
@@ -1,12 +1,14 @@
-import React from 'react';
+import React, { useState, useEffect } from 'react';
 import { WalletMultiButton } from '@solana/wallet-adapter-react-ui';
 import { Apartment, Profile } from '@/lib/schema';
+import { EscrowTerms, DEFAULT_ESCROW_TERMS } from '../operations/escrowOperations';
+import { percentToBps } from '../utils/amounts';
 
 interface WaitingForInitializationPageProps {
   apartment: Apartment | null;
   apartmentOwnerProfile: Profile | null;
   onRefresh: () => void;
-  onInitialize: () => void;
+  onInitialize: (terms: EscrowTerms) => void;
   isOwner: boolean;
   initializing: boolean;
 }
@@ -19,6 +21,79 @@ export const WaitingForInitializationPage: React.FC<WaitingForInitializationPage
   isOwner,
   initializing
 }) => {
+  const [form, setForm] = useState({
+    requiredStake: '',
+    referralReward: '',
+    referralLimit: '',
+    decisionWindowDays: String(DEFAULT_ESCROW_TERMS.decisionWindowDays),
+    disputeWindowDays: String(DEFAULT_ESCROW_TERMS.disputeWindowDays),
+    lessorPercent: String(DEFAULT_ESCROW_TERMS.slashSplit.lessorBps / 100),
+    penaltyWalletPercent: String(DEFAULT_ESCROW_TERMS.slashSplit.penaltyWalletBps / 100),
+    referrerPercent: String(DEFAULT_ESCROW_TERMS.slashSplit.referrerBps / 100),
+    discountPercentPerStake: String(DEFAULT_ESCROW_TERMS.stakeDiscount.bpsPerCompletedStake / 100),
+    maxDiscountPercent: String(DEFAULT_ESCROW_TERMS.stakeDiscount.maxDiscountBps / 100),
+  });
+  const [formError, setFormError] = useState<string | null>(null);
+
+  // Prefill the listing's terms once the apartment has loaded
+  useEffect(() => {
+    if (!apartment) return;
+    setForm((current) => ({
+      ...current,
+      requiredStake: String(apartment.stake),
+      referralReward: String(apartment.reward),
+      referralLimit: String(apartment.referral_limit),
+    }));
+  }, [apartment]);
+
+  const handleSubmit = () => {
+    try {
+      const terms: EscrowTerms = {
+        requiredStake: form.requiredStake,
+        referralReward: form.referralReward,
+        referralLimit: parseInt(form.referralLimit, 10),
+        decisionWindowDays: parseInt(form.decisionWindowDays, 10),
+        disputeWindowDays: parseInt(form.disputeWindowDays, 10),
+        slashSplit: {
+          lessorBps: percentToBps(form.lessorPercent),
+          penaltyWalletBps: percentToBps(form.penaltyWalletPercent),
+          referrerBps: percentToBps(form.referrerPercent),
+        },
+        stakeDiscount: {
+          bpsPerCompletedStake: percentToBps(form.discountPercentPerStake),
+          maxDiscountBps: percentToBps(form.maxDiscountPercent),
+        },
+      };
+      const splitTotal = terms.slashSplit.lessorBps + terms.slashSplit.penaltyWalletBps + terms.slashSplit.referrerBps;
+      if (splitTotal !== 10_000) {
+        throw new Error('Slash split must add up to 100%');
+      }
+      if (!Number.isInteger(terms.referralLimit) || terms.referralLimit < 0) {
+        throw new Error('Referral limit must be a whole number');
+      }
+      if ([terms.decisionWindowDays, terms.disputeWindowDays].some((n) => !Number.isInteger(n) || n <= 0)) {
+        throw new Error('Decision and dispute windows must be at least one day');
+      }
+      setFormError(null);
+      onInitialize(terms);
+    } catch (error) {
+      setFormError(error instanceof Error ? error.message : String(error));
+    }
+  };
+
+  const fields: { key: keyof typeof form; label: string }[] = [
+    { key: 'requiredStake', label: 'Required stake (SOL)' },
+    { key: 'referralReward', label: 'Referral reward (SOL)' },
+    { key: 'referralLimit', label: 'Referral limit' },
+    { key: 'decisionWindowDays', label: 'Decision window (days)' },
+    { key: 'disputeWindowDays', label: 'Dispute window (days)' },
+    { key: 'lessorPercent', label: 'Slash to lessor (%)' },
+    { key: 'penaltyWalletPercent', label: 'Slash to penalty wallet (%)' },
+    { key: 'referrerPercent', label: 'Slash to referrer (%)' },
+    { key: 'discountPercentPerStake', label: 'Stake discount per completed lease (%)' },
+    { key: 'maxDiscountPercent', label: 'Max stake discount (%)' },
+  ];
+
   return (
     <div className="min-h-screen bg-gray-50 flex items-center justify-center">
       <div className="text-center max-w-md mx-auto">
@@ -42,11 +117,28 @@ export const WaitingForInitializationPage: React.FC<WaitingForInitializationPage
             </div>
           )}
         </div>
+        {isOwner && (
+          <div className="bg-white rounded-lg shadow p-4 mb-6 text-left space-y-2">
+            {fields.map(({ key, label }) => (
+              <label key={key} className="flex justify-between items-center gap-2 text-sm">
+                <span className="text-gray-700">{label}</span>
+                <input
+                  type="text"
+                  inputMode="decimal"
+                  value={form[key]}
+                  onChange={(e) => setForm({ ...form, [key]: e.target.value })}
+                  className="w-28 px-2 py-1 border rounded"
+                />
+              </label>
+            ))}
+            {formError && <p className="text-sm text-red-600">{formError}</p>}
+          </div>
+        )}
         <div className="flex gap-4 justify-center">
           <WalletMultiButton />
           {isOwner && (
             <button
-              onClick={onInitialize}
+              onClick={handleSubmit}
               disabled={initializing}
               className="px-4 py-2 bg-green-600 text-white rounded-lg hover:bg-green-700 disabled:opacity-50"
             >
@@ -0,0 +1,29 @@
+import { BN } from '@coral-xyz/anchor';
+
+// Parse a non-negative decimal string into integer base units without going through floats
+export const parseDecimalUnits = (value: string, decimals: number): BN => {
+  const trimmed = value.trim();
+  const match = /^(\d*)(?:\.(\d*))?$/.exec(trimmed);
+  if (!match || trimmed === '' || trimmed === '.') {
+    throw new Error(`Invalid amount: ${value}`);
+  }
+
+  const [, whole, fraction = ''] = match;
+  if (fraction.length > decimals) {
+    throw new Error(`Invalid amount: ${value} has more than ${decimals} decimals`);
+  }
+
+  return new BN((whole || '0') + fraction.padEnd(decimals, '0'));
+};
+
+// SOL amount as entered by the user, in lamports
+export const solToLamports = (sol: string): BN => parseDecimalUnits(sol, 9);
+
+// Percentage as entered by the user (up to two decimals), in basis points
+export const percentToBps = (percent: string): number => {
+  const bps = parseDecimalUnits(percent, 2);
+  if (bps.gtn(10_000)) {
+    throw new Error(`Invalid percentage: ${percent}`);
+  }
+  return bps.toNumber();
+};
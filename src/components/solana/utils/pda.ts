@@ -21,4 +21,50 @@ export const getStakeRecordPDA = (apartmentId: string, profileId: string): Addre
     PROGRAM_ID
   );
   return address(pda.toBase58() as Address);
-}; 
\ No newline at end of file
+}; 
+
+export const getSlashDisputePDA = (apartmentId: string, profileId: string): Address => {
+  const apartmentHash = hashString(apartmentId);
+  const profileHash = hashString(profileId);
+  const [pda] = PublicKey.findProgramAddressSync(
+    [Buffer.from('dispute'), apartmentHash, profileHash],
+    PROGRAM_ID
+  );
+  return address(pda.toBase58() as Address);
+};
+
+export const getReputationPDA = (profileId: string, owner: PublicKey): Address => {
+  const profileHash = hashString(profileId);
+  const [pda] = PublicKey.findProgramAddressSync(
+    [Buffer.from('reputation'), profileHash, owner.toBuffer()],
+    PROGRAM_ID
+  );
+  return address(pda.toBase58() as Address);
+};
+
+export const getReferralPDA = (apartmentId: string, profileId: string): Address => {
+  const apartmentHash = hashString(apartmentId);
+  const profileHash = hashString(profileId);
+  const [pda] = PublicKey.findProgramAddressSync(
+    [Buffer.from('referral'), apartmentHash, profileHash],
+    PROGRAM_ID
+  );
+  return address(pda.toBase58() as Address);
+};
+
+export const getReferrerCounterPDA = (apartmentId: string, referrer: PublicKey): Address => {
+  const apartmentHash = hashString(apartmentId);
+  const [pda] = PublicKey.findProgramAddressSync(
+    [Buffer.from('referrer_count'), apartmentHash, referrer.toBuffer()],
+    PROGRAM_ID
+  );
+  return address(pda.toBase58() as Address);
+};
+
+export const getProtocolConfigPDA = (): Address => {
+  const [pda] = PublicKey.findProgramAddressSync(
+    [Buffer.from('config')],
+    PROGRAM_ID
+  );
+  return address(pda.toBase58() as Address);
+};